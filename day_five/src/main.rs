@@ -1,38 +1,342 @@
-use std::{collections::VecDeque, fs};
+use std::{collections::VecDeque, env, fs};
+
+const USAGE: &str = "Usage: cargo run -- <command>
+    pt1
+    pt2 [reverse]
+    convert <from> <to> <value>
+    invert <from> <to> <value>
+    flatten [<from> <to>]
+    compare <almanac file>
+    check
+    trace [json] [<seed>...]
+    export <csv | dot>";
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+
     let input = fs::read_to_string("src/input.txt").unwrap();
-    let map = EntireMap::from(input.as_str());
-    println!("{}", map.lowest_location());
+    let map = match EntireMap::parse(&input) {
+        Ok(map) => map,
+        Err(e) => return println!("{}", e),
+    };
+    match args[1].as_str() {
+        "pt1" | "pt2" => {
+            let lowest = match (args[1].as_str(), args.get(2).map(|a| a.as_str())) {
+                ("pt1", _) => map.lowest_location(),
+                (_, Some("reverse")) => map.lowest_range_location_reverse(),
+                _ => map.lowest_range_location(),
+            };
+            match lowest {
+                Ok(location) => println!("{}", location),
+                Err(e) => println!("{}", e),
+            }
+        }
+        "convert" if args.len() == 5 => match args[4].parse() {
+            Ok(value) => match map.convert(value, &args[2], &args[3]) {
+                Ok(converted) => println!("{}", converted),
+                Err(e) => println!("{}", e),
+            },
+            Err(_) => println!("{} is not a number", args[4]),
+        },
+        "invert" if args.len() == 5 => match args[4].parse() {
+            Ok(value) => match map.preimage(&[(value, 1)], &args[3], &args[2]) {
+                Ok(ranges) => ranges
+                    .iter()
+                    .for_each(|(start, length)| println!("{} {}", start, length)),
+                Err(e) => println!("{}", e),
+            },
+            Err(_) => println!("{} is not a number", args[4]),
+        },
+        "flatten" => {
+            let (from, to) = match &args[2..] {
+                [from, to] => (from.as_str(), to.as_str()),
+                _ => ("seed", "location"),
+            };
+            match map.flatten(from, to) {
+                Ok(flat) => print!("{}", flat),
+                Err(e) => println!("{}", e),
+            }
+        }
+        "compare" if args.len() == 3 => {
+            let other = match fs::read_to_string(&args[2])
+                .map_err(|e| e.to_string())
+                .and_then(|other| EntireMap::parse(&other).map_err(|e| e.to_string()))
+            {
+                Ok(other) => other,
+                Err(e) => return println!("{}", e),
+            };
+            match (
+                map.flatten("seed", "location"),
+                other.flatten("seed", "location"),
+            ) {
+                (Ok(ours), Ok(theirs)) => ours
+                    .differences(&theirs)
+                    .iter()
+                    .for_each(|(start, length)| println!("{} {}", start, length)),
+                (Err(e), _) | (_, Err(e)) => println!("{}", e),
+            }
+        }
+        "check" => {
+            let issues = validate_almanac(&input);
+            if issues.is_empty() {
+                println!("{} maps, no issues", map.maps.len());
+            }
+            issues.iter().for_each(|issue| println!("{}", issue));
+        }
+        "trace" => {
+            let json = args.get(2).is_some_and(|a| a == "json");
+            let picked: Result<Vec<u64>, _> = args[if json { 3 } else { 2 }..]
+                .iter()
+                .map(|a| a.parse())
+                .collect();
+            let seeds = match picked {
+                Ok(picked) if picked.is_empty() => map.seeds.clone(),
+                Ok(picked) => picked,
+                Err(_) => return println!("seeds must be numbers"),
+            };
+            let traces: Result<Vec<SeedTrace>, _> = seeds.iter().map(|s| map.trace(*s)).collect();
+            match traces {
+                Ok(traces) if json => {
+                    let traces: Vec<String> = traces.iter().map(|t| t.to_json()).collect();
+                    println!("[{}]", traces.join(","))
+                }
+                Ok(traces) => traces.iter().for_each(|t| print!("{}", t)),
+                Err(e) => println!("{}", e),
+            }
+        }
+        "export" => match args.get(2).map(|a| a.as_str()) {
+            Some("csv") => print!("{}", map.to_csv()),
+            Some("dot") => print!("{}", map.to_dot()),
+            _ => println!("{}", USAGE),
+        },
+        _ => println!("{}", USAGE),
+    }
 }
 
 type AlmanacMap = (u64, u64, u64);
 
-#[derive(Debug, Clone)]
-struct AlmanacRanges {
-    destination_range: Vec<u64>,
-    source_range: Vec<u64>,
-}
+/// `(start, length)` of a run of consecutive values.
+type ValueRange = (u64, u64);
 
-#[derive(Debug, Clone)]
-struct DestinationSourceMap {
-    destination: u64,
-    source: u64,
+/// `length` consecutive values from `source_start` map onto the values from
+/// `dest_start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Interval {
+    source_start: u64,
+    dest_start: u64,
+    length: u64,
 }
 
-#[derive(Debug, Clone)]
-struct DestinationSourceMapVec(Vec<DestinationSourceMap>);
+/// One almanac map as non-overlapping intervals sorted by `source_start`.
+/// Values outside every interval map to themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DestinationSourceMapVec(Vec<Interval>);
 
+/// A map between two categories, named by its `<from>-to-<to> map:` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CategoryMap {
+    from: String,
+    to: String,
+    map: DestinationSourceMapVec,
+    /// Each entry as listed, with its 1-based input line, before overlaps
+    /// are resolved into `map`.
+    entries: Vec<(usize, AlmanacMap)>,
+}
+
+/// The seeds plus every map in the almanac, in input order. The maps form a
+/// directed acyclic graph of categories.
 #[derive(Debug, Clone)]
 struct EntireMap {
     seeds: Vec<u64>,
-    seed_to_soil: DestinationSourceMapVec,
-    soil_to_fertilizer: DestinationSourceMapVec,
-    fertilizer_to_water: DestinationSourceMapVec,
-    water_to_light: DestinationSourceMapVec,
-    light_to_temp: DestinationSourceMapVec,
-    temp_to_humidity: DestinationSourceMapVec,
-    humidity_to_location: DestinationSourceMapVec,
+    maps: Vec<CategoryMap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AlmanacError {
+    /// A section without a `<from>-to-<to> map:` header, or no `seeds:` line.
+    Malformed(String),
+    /// No chain of maps leads from one category to the other.
+    MissingLink { from: String, to: String },
+    /// The maps loop back to a category, listed from start to repeat.
+    Cycle(Vec<String>),
+    /// There are no seeds, or no seed ranges, to find a lowest location for.
+    NoSeeds,
+    /// A map entry whose source or destination range runs past `u64::MAX`.
+    Overflow(String),
+}
+
+/// One map applied during a `SeedTrace`: the category reached, its value,
+/// and the input line and entry used as listed, or `None` for an identity
+/// pass-through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TraceStep {
+    category: String,
+    value: u64,
+    entry: Option<(usize, AlmanacMap)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SeedTrace {
+    seed: u64,
+    steps: Vec<TraceStep>,
+}
+
+impl SeedTrace {
+    fn to_json(&self) -> String {
+        let escape = |name: &str| name.replace('\\', "\\\\").replace('"', "\\\"");
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| {
+                let entry = match step.entry {
+                    Some((line, (dest, source, length))) => format!(
+                        "{{\"line\":{},\"dest\":{},\"source\":{},\"length\":{}}}",
+                        line, dest, source, length
+                    ),
+                    None => "null".to_string(),
+                };
+                format!(
+                    "{{\"category\":\"{}\",\"value\":{},\"entry\":{}}}",
+                    escape(&step.category),
+                    step.value,
+                    entry
+                )
+            })
+            .collect();
+        format!("{{\"seed\":{},\"steps\":[{}]}}", self.seed, steps.join(","))
+    }
+}
+
+impl std::fmt::Display for SeedTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "seed {}", self.seed)?;
+        self.steps.iter().try_for_each(|step| match step.entry {
+            Some((line, (dest, source, length))) => writeln!(
+                f,
+                "  {} {} (line {}: {} {} {})",
+                step.category, step.value, line, dest, source, length
+            ),
+            None => writeln!(f, "  {} {} (identity)", step.category, step.value),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AlmanacIssueKind {
+    /// A line in a map section that is not three numbers.
+    MalformedEntry,
+    ZeroLength,
+    /// `source start + length` does not fit in a `u64`.
+    SourceOverflow,
+    /// `destination start + length` does not fit in a `u64`.
+    DestinationOverflow,
+    /// Shares source values with the entry on the given line.
+    OverlappingSource(usize),
+    /// Shares destination values with the entry on the given line, so some
+    /// destinations have two sources.
+    OverlappingDestination(usize),
+}
+
+/// A problem with the map entry on 1-based input line `line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AlmanacIssue {
+    line: usize,
+    map: String,
+    kind: AlmanacIssueKind,
+}
+
+impl std::fmt::Display for AlmanacIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {} ({}): ", self.line, self.map)?;
+        match &self.kind {
+            AlmanacIssueKind::MalformedEntry => write!(f, "expected '<dest> <source> <length>'"),
+            AlmanacIssueKind::ZeroLength => write!(f, "zero-length entry"),
+            AlmanacIssueKind::SourceOverflow => write!(f, "source range overflows u64"),
+            AlmanacIssueKind::DestinationOverflow => write!(f, "destination range overflows u64"),
+            AlmanacIssueKind::OverlappingSource(other) => {
+                write!(f, "source range overlaps line {}", other)
+            }
+            AlmanacIssueKind::OverlappingDestination(other) => {
+                write!(f, "destination range overlaps line {}", other)
+            }
+        }
+    }
+}
+
+/// Checks each map for entries that `lookup_source` would resolve silently
+/// or arbitrarily: malformed or empty entries, ranges running past
+/// `u64::MAX`, and source or destination ranges shared by two entries.
+fn validate_almanac(input: &str) -> Vec<AlmanacIssue> {
+    let mut issues = vec![];
+    let mut sections: Vec<(String, Vec<(usize, AlmanacMap)>)> = vec![];
+    input.lines().enumerate().for_each(|(i, l)| {
+        let (line, l) = (i + 1, l.trim());
+        if let Some(header) = l.strip_suffix(" map:") {
+            sections.push((header.to_string(), vec![]));
+            return;
+        }
+        let Some((map, entries)) = sections.last_mut() else {
+            return;
+        };
+        if l.is_empty() {
+            return;
+        }
+        match try_map_from_str(l) {
+            Some(entry) => entries.push((line, entry)),
+            None => issues.push(AlmanacIssue {
+                line,
+                map: map.clone(),
+                kind: AlmanacIssueKind::MalformedEntry,
+            }),
+        }
+    });
+
+    for (map, entries) in sections.iter() {
+        let issue = |line, kind| AlmanacIssue {
+            line,
+            map: map.clone(),
+            kind,
+        };
+        for &(line, (dest, source, length)) in entries.iter() {
+            if length == 0 {
+                issues.push(issue(line, AlmanacIssueKind::ZeroLength));
+            }
+            if source.checked_add(length).is_none() {
+                issues.push(issue(line, AlmanacIssueKind::SourceOverflow));
+            }
+            if dest.checked_add(length).is_none() {
+                issues.push(issue(line, AlmanacIssueKind::DestinationOverflow));
+            }
+        }
+        // Sweeps entries by start, comparing each with the furthest-reaching
+        // earlier one. Ends are `u128` so overflowing entries still compare.
+        let mut sweep = |start: fn(&AlmanacMap) -> u64, kind: fn(usize) -> AlmanacIssueKind| {
+            let mut sorted: Vec<&(usize, AlmanacMap)> =
+                entries.iter().filter(|(_, e)| e.2 > 0).collect();
+            sorted.sort_by_key(|(line, e)| (start(e), *line));
+            let mut furthest: Option<(usize, u128)> = None;
+            for (line, entry) in sorted {
+                let end = start(entry) as u128 + entry.2 as u128;
+                match furthest {
+                    Some((other, other_end)) if (start(entry) as u128) < other_end => {
+                        issues.push(issue(*line, kind(other)));
+                        if end > other_end {
+                            furthest = Some((*line, end));
+                        }
+                    }
+                    _ => furthest = Some((*line, end)),
+                }
+            }
+        };
+        sweep(|e| e.1, AlmanacIssueKind::OverlappingSource);
+        sweep(|e| e.0, AlmanacIssueKind::OverlappingDestination);
+    }
+    issues.sort_by_key(|i| i.line);
+    issues
 }
 
 fn try_map_from_str(str: &str) -> Option<AlmanacMap> {
@@ -53,134 +357,609 @@ fn try_map_from_str(str: &str) -> Option<AlmanacMap> {
     }
 }
 
-impl From<AlmanacMap> for AlmanacRanges {
+impl From<AlmanacMap> for Interval {
     fn from(value: AlmanacMap) -> Self {
-        let range_iter = (0..value.2).into_iter();
-        let destination_range = range_iter.clone().fold(vec![], |mut range, i| {
-            range.push(value.0 + i);
-            range
-        });
-        let source_range = range_iter.fold(vec![], |mut range, i| {
-            range.push(value.1 + i);
-            range
-        });
-        AlmanacRanges {
-            destination_range,
-            source_range,
+        Interval {
+            source_start: value.1,
+            dest_start: value.0,
+            length: value.2,
         }
     }
 }
 
-impl TryFrom<Vec<AlmanacRanges>> for DestinationSourceMapVec {
-    type Error = Box<dyn std::error::Error>;
-    fn try_from(value: Vec<AlmanacRanges>) -> Result<Self, Self::Error> {
-        let return_vec = value.into_iter().fold(vec![], |mut outer_mapvec, r| {
-            outer_mapvec.append(r.destination_range.into_iter().enumerate().fold(
-                &mut vec![],
-                |mapvec, (i, d)| {
-                    let source_map = DestinationSourceMap {
-                        destination: d,
-                        source: r.source_range[i],
-                    };
-                    mapvec.push(source_map);
-                    mapvec
-                },
-            ));
-            outer_mapvec
-        });
-        Ok(Self(return_vec))
+impl Interval {
+    /// Whether `source` falls in this interval, without overflowing on
+    /// intervals that reach `u64::MAX`.
+    fn contains(&self, source: u64) -> bool {
+        source >= self.source_start && source - self.source_start < self.length
     }
 }
 
+/// Where entries overlap, including entries with the same source start, the
+/// one listed first wins, as in a first-match scan of the input. Later entries
+/// keep only the values no earlier entry covers, so a binary search over the
+/// stored intervals finds the same entry the scan would.
+impl From<Vec<AlmanacMap>> for DestinationSourceMapVec {
+    fn from(value: Vec<AlmanacMap>) -> Self {
+        let mut intervals: Vec<Interval> = vec![];
+        for entry in value.into_iter().map(Interval::from) {
+            // Ends are `u128` so entries reaching `u64::MAX` still fit.
+            let end = |i: &Interval| i.source_start as u128 + i.length as u128;
+            let mut claimed: Vec<(u128, u128)> = intervals
+                .iter()
+                .filter(|i| {
+                    (i.source_start as u128) < end(&entry) && end(i) > entry.source_start as u128
+                })
+                .map(|i| (i.source_start as u128, end(i)))
+                .collect();
+            claimed.sort_unstable();
+            claimed.push((end(&entry), end(&entry)));
+            let mut start = entry.source_start as u128;
+            for (claimed_start, claimed_end) in claimed {
+                if claimed_start > start {
+                    let offset = (start - entry.source_start as u128) as u64;
+                    intervals.push(Interval {
+                        source_start: start as u64,
+                        dest_start: entry.dest_start + offset,
+                        length: (claimed_start - start) as u64,
+                    });
+                }
+                start = start.max(claimed_end);
+            }
+        }
+        intervals.sort_by_key(|i| i.source_start);
+        Self(intervals)
+    }
+}
+
+impl std::fmt::Display for DestinationSourceMapVec {
+    /// Writes one `dest source length` line per interval, as in the input.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|i| writeln!(f, "{} {} {}", i.dest_start, i.source_start, i.length))
+    }
+}
+
+impl std::fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(section) => write!(f, "malformed almanac section: {}", section),
+            Self::MissingLink { from, to } => write!(f, "no maps lead from {} to {}", from, to),
+            Self::Cycle(categories) => write!(f, "maps form a cycle: {}", categories.join(" -> ")),
+            Self::NoSeeds => write!(f, "the almanac lists no seeds"),
+            Self::Overflow(entry) => write!(f, "map entry runs past u64::MAX: {}", entry),
+        }
+    }
+}
+
+impl std::error::Error for AlmanacError {}
+
 impl From<&str> for EntireMap {
     fn from(value: &str) -> Self {
-        let mut maps: VecDeque<&str> = value.split("\n\n").map(|s| s.trim()).collect();
-        let seeds: Vec<u64> = maps
-            .pop_front()
-            .unwrap()
-            .split_once(':')
-            .expect("failed to split on :")
-            .1
+        Self::parse(value).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl EntireMap {
+    /// Parses the seeds and every map section, keying maps by the categories
+    /// in their headers rather than their position. Entries whose source or
+    /// destination range runs past `u64::MAX` are rejected, so lookups in
+    /// either direction never overflow.
+    fn parse(value: &str) -> Result<Self, AlmanacError> {
+        // Pairs each trimmed section with the input line it starts on.
+        let mut line = 1;
+        let mut sections: VecDeque<(usize, &str)> = value
+            .split("\n\n")
+            .map(|s| {
+                let start = line + s[..s.len() - s.trim_start().len()].matches('\n').count();
+                line += s.matches('\n').count() + 2;
+                (start, s.trim())
+            })
+            .collect();
+        let (_, seeds_section) = sections.pop_front().unwrap_or_default();
+        let seeds: Vec<u64> = seeds_section
+            .strip_prefix("seeds:")
+            .ok_or_else(|| AlmanacError::Malformed(seeds_section.to_string()))?
             .split_whitespace()
             .filter_map(|s| s.parse().ok())
             .collect();
 
-        println!("{:?}", maps);
-        let vec_maps: Vec<Vec<AlmanacMap>> = maps
+        let maps = sections
             .into_iter()
-            .map(|m| {
-                m.split_once(':')
-                    .expect("failed to split on :")
-                    .1
+            .filter(|(_, section)| !section.is_empty())
+            .map(|(start, section)| {
+                let malformed = || AlmanacError::Malformed(section.to_string());
+                let (header, body) = section.split_once(':').ok_or_else(malformed)?;
+                let (from, to) = header
+                    .trim()
+                    .strip_suffix(" map")
+                    .and_then(|names| names.split_once("-to-"))
+                    .ok_or_else(malformed)?;
+                let fits = |start: u64, length: u64| start as u128 + length as u128 <= 1 << 64;
+                // The body starts on the header's line, after its `:`.
+                let entries: Vec<(usize, AlmanacMap)> = body
                     .lines()
-                    .filter_map(|l| try_map_from_str(l))
-                    .collect()
+                    .enumerate()
+                    .filter_map(|(i, l)| try_map_from_str(l).map(|entry| (start + i, l, entry)))
+                    .map(|(line, l, entry @ (dest, source, length))| {
+                        match fits(dest, length) && fits(source, length) {
+                            true => Ok((line, entry)),
+                            false => Err(AlmanacError::Overflow(l.trim().to_string())),
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(CategoryMap {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    map: DestinationSourceMapVec::from(
+                        entries.iter().map(|(_, entry)| *entry).collect::<Vec<_>>(),
+                    ),
+                    entries,
+                })
             })
-            .collect();
-        let ranges: VecDeque<Vec<AlmanacRanges>> = vec_maps
+            .collect::<Result<Vec<_>, _>>()?;
+        let almanac = EntireMap { seeds, maps };
+        almanac.check_acyclic()?;
+        Ok(almanac)
+    }
+
+    /// Fails with the first cycle found by a depth-first walk from each
+    /// category.
+    fn check_acyclic(&self) -> Result<(), AlmanacError> {
+        fn visit<'a>(
+            almanac: &'a EntireMap,
+            category: &'a str,
+            path: &mut Vec<&'a str>,
+            done: &mut Vec<&'a str>,
+        ) -> Result<(), AlmanacError> {
+            if let Some(start) = path.iter().position(|c| *c == category) {
+                let mut cycle: Vec<String> = path[start..].iter().map(|c| c.to_string()).collect();
+                cycle.push(category.to_string());
+                return Err(AlmanacError::Cycle(cycle));
+            }
+            if done.contains(&category) {
+                return Ok(());
+            }
+            path.push(category);
+            for m in almanac.maps.iter().filter(|m| m.from == category) {
+                visit(almanac, &m.to, path, done)?;
+            }
+            path.pop();
+            done.push(category);
+            Ok(())
+        }
+        let mut done = vec![];
+        self.maps
+            .iter()
+            .try_for_each(|m| visit(self, &m.from, &mut vec![], &mut done))
+    }
+
+    /// The maps to apply, in order, to convert a `from` value into a `to`
+    /// value, choosing the route through the fewest maps.
+    fn route(&self, from: &str, to: &str) -> Result<Vec<&DestinationSourceMapVec>, AlmanacError> {
+        Ok(self
+            .route_steps(from, to)?
+            .into_iter()
+            .map(|m| &m.map)
+            .collect())
+    }
+
+    /// `route`, keeping the category names of each map.
+    fn route_steps(&self, from: &str, to: &str) -> Result<Vec<&CategoryMap>, AlmanacError> {
+        let mut reached_by: Vec<(&str, Option<usize>)> = vec![(from, None)];
+        let mut frontier = VecDeque::from([from]);
+        while let Some(category) = frontier.pop_front() {
+            if category == to {
+                break;
+            }
+            self.maps
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.from == category)
+                .for_each(|(idx, m)| {
+                    if !reached_by.iter().any(|(c, _)| *c == m.to) {
+                        reached_by.push((&m.to, Some(idx)));
+                        frontier.push_back(&m.to);
+                    }
+                });
+        }
+        let mut route = vec![];
+        let mut category = to;
+        loop {
+            match reached_by.iter().find(|(c, _)| *c == category) {
+                Some((_, Some(idx))) => {
+                    route.push(&self.maps[*idx]);
+                    category = &self.maps[*idx].from;
+                }
+                Some((_, None)) => break,
+                None => {
+                    return Err(AlmanacError::MissingLink {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    })
+                }
+            }
+        }
+        route.reverse();
+        Ok(route)
+    }
+
+    /// Converts a `from` category value into its `to` category value.
+    fn convert(&self, value: u64, from: &str, to: &str) -> Result<u64, AlmanacError> {
+        let route = self.route(from, to)?;
+        Ok(route.iter().fold(value, |v, map| map.lookup_source(v)))
+    }
+
+    /// The `from` category ranges that convert into any value of `ranges` in
+    /// the `to` category.
+    fn preimage(
+        &self,
+        ranges: &[ValueRange],
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<ValueRange>, AlmanacError> {
+        let route = self.route(from, to)?;
+        Ok(route
+            .into_iter()
+            .rev()
+            .fold(merge_ranges(ranges.to_vec()), |ranges, map| {
+                map.preimage_ranges(&ranges)
+            }))
+    }
+
+    /// Every map on the route from `from` to `to` composed into one.
+    fn flatten(&self, from: &str, to: &str) -> Result<DestinationSourceMapVec, AlmanacError> {
+        let route = self.route(from, to)?;
+        Ok(route
+            .into_iter()
+            .fold(DestinationSourceMapVec(vec![]), |flat, map| flat.then(map)))
+    }
+
+    /// Same answer as `lowest_range_location`, found by bisecting the
+    /// location space: keep the lower half whenever some seed range maps
+    /// into it.
+    fn lowest_range_location_reverse(&self) -> Result<u64, AlmanacError> {
+        let seeds = merge_ranges(self.seed_ranges()?);
+        let reaches_seeds = |locations: ValueRange| -> Result<bool, AlmanacError> {
+            let found = self.preimage(&[locations], "seed", "location")?;
+            let end = |start: u64, length: u64| start as u128 + length as u128;
+            Ok(found.iter().any(|&(start, length)| {
+                seeds
+                    .iter()
+                    .any(|&(s, l)| (start as u128) < end(s, l) && (s as u128) < end(start, length))
+            }))
+        };
+        let (mut start, mut length) = (0, u64::MAX);
+        if !reaches_seeds((start, length))? {
+            return match reaches_seeds((u64::MAX, 1))? {
+                true => Ok(u64::MAX),
+                false => Err(AlmanacError::NoSeeds),
+            };
+        }
+        while length > 1 {
+            let lower = length / 2;
+            if reaches_seeds((start, lower))? {
+                length = lower;
+            } else {
+                start += lower;
+                length -= lower;
+            }
+        }
+        Ok(start)
+    }
+
+    /// One CSV row per interval of every map, in input order.
+    fn to_csv(&self) -> String {
+        let field = |name: &str| match name.contains([',', '"', '\n']) {
+            true => format!("\"{}\"", name.replace('"', "\"\"")),
+            false => name.to_string(),
+        };
+        let mut out = String::from("from,to,dest_start,source_start,length\n");
+        self.maps.iter().for_each(|m| {
+            m.map.0.iter().for_each(|i| {
+                out.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    field(&m.from),
+                    field(&m.to),
+                    i.dest_start,
+                    i.source_start,
+                    i.length
+                ))
+            })
+        });
+        out
+    }
+
+    /// The category graph, with each map as an edge labelled by its number
+    /// of intervals.
+    fn to_dot(&self) -> String {
+        let escape = |name: &str| name.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut categories: Vec<&str> = vec![];
+        self.maps.iter().for_each(|m| {
+            [m.from.as_str(), m.to.as_str()].into_iter().for_each(|c| {
+                if !categories.contains(&c) {
+                    categories.push(c)
+                }
+            })
+        });
+        let mut out = String::from("digraph almanac {\n");
+        categories.into_iter().for_each(|c| {
+            out.push_str(&format!("    \"{}\";\n", escape(c)));
+        });
+        self.maps.iter().for_each(|m| {
+            let count = m.map.0.len();
+            out.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{} interval{}\"];\n",
+                escape(&m.from),
+                escape(&m.to),
+                count,
+                if count == 1 { "" } else { "s" }
+            ));
+        });
+        out.push_str("}\n");
+        out
+    }
+
+    /// Each value `seed` takes on the way to `location`, and the map entry
+    /// that produced it: the first listed entry covering the value, as the
+    /// overlap resolution picks.
+    fn trace(&self, seed: u64) -> Result<SeedTrace, AlmanacError> {
+        let mut value = seed;
+        let steps = self
+            .route_steps("seed", "location")?
             .into_iter()
             .map(|m| {
-                m.into_iter()
-                    .map(|inner_m| AlmanacRanges::from(inner_m))
-                    .collect()
+                let entry = m
+                    .entries
+                    .iter()
+                    .find(|(_, e)| Interval::from(*e).contains(value))
+                    .copied();
+                value = m.map.lookup_source(value);
+                TraceStep {
+                    category: m.to.clone(),
+                    value,
+                    entry,
+                }
             })
             .collect();
-        EntireMap {
-            seeds,
-            seed_to_soil: DestinationSourceMapVec::try_from(ranges.get(0).unwrap().to_owned())
-                .unwrap(),
-            soil_to_fertilizer: DestinationSourceMapVec::try_from(
-                ranges.get(1).unwrap().to_owned(),
-            )
-            .unwrap(),
-            fertilizer_to_water: DestinationSourceMapVec::try_from(
-                ranges.get(2).unwrap().to_owned(),
-            )
-            .unwrap(),
-            water_to_light: DestinationSourceMapVec::try_from(ranges.get(3).unwrap().to_owned())
-                .unwrap(),
-            light_to_temp: DestinationSourceMapVec::try_from(ranges.get(4).unwrap().to_owned())
-                .unwrap(),
-            temp_to_humidity: DestinationSourceMapVec::try_from(ranges.get(5).unwrap().to_owned())
-                .unwrap(),
-            humidity_to_location: DestinationSourceMapVec::try_from(
-                ranges.get(6).unwrap().to_owned(),
-            )
-            .unwrap(),
-        }
+        Ok(SeedTrace { seed, steps })
     }
-}
 
-impl EntireMap {
-    fn seed_locations(&self) -> Vec<(u64, u64)> {
-        self.seeds.iter().fold(vec![], |mut ret_tup, s| {
-            let soil = self.seed_to_soil.lookup_source(s.clone());
-            let fert = self.soil_to_fertilizer.lookup_source(soil);
-            let water = self.fertilizer_to_water.lookup_source(fert);
-            let light = self.water_to_light.lookup_source(water);
-            let temp = self.light_to_temp.lookup_source(light);
-            let hum = self.temp_to_humidity.lookup_source(temp);
-            let loc = self.humidity_to_location.lookup_source(hum);
+    fn seed_locations(&self) -> Result<Vec<(u64, u64)>, AlmanacError> {
+        let route = self.route("seed", "location")?;
+        Ok(self.seeds.iter().fold(vec![], |mut ret_tup, s| {
+            let loc = route.iter().fold(*s, |v, map| map.lookup_source(v));
             ret_tup.push((loc, *s));
             ret_tup
-        })
+        }))
+    }
+
+    /// The `seeds:` line read as `(start, length)` pairs. A start without a
+    /// length makes the line malformed.
+    fn seed_ranges(&self) -> Result<Vec<ValueRange>, AlmanacError> {
+        self.seeds
+            .chunks(2)
+            .map(|pair| match pair {
+                [start, length] => Ok((*start, *length)),
+                _ => {
+                    let seeds: Vec<String> = self.seeds.iter().map(|s| s.to_string()).collect();
+                    Err(AlmanacError::Malformed(format!(
+                        "seeds: {}",
+                        seeds.join(" ")
+                    )))
+                }
+            })
+            .collect()
+    }
+
+    /// Lowest location for any seed in `seed_ranges`, found by pushing whole
+    /// ranges through each map instead of individual seeds.
+    fn lowest_range_location(&self) -> Result<u64, AlmanacError> {
+        let seeds = merge_ranges(self.seed_ranges()?);
+        self.route("seed", "location")?
+            .into_iter()
+            .fold(seeds, |ranges, map| {
+                merge_ranges(map.lookup_ranges(&ranges))
+            })
+            .first()
+            .map(|range| range.0)
+            .ok_or(AlmanacError::NoSeeds)
     }
 
-    fn lowest_location(&self) -> u64 {
-        self.seed_locations()
+    fn lowest_location(&self) -> Result<u64, AlmanacError> {
+        self.seed_locations()?
             .into_iter()
             .min_by_key(|x| x.0)
-            .unwrap()
-            .0
+            .map(|x| x.0)
+            .ok_or(AlmanacError::NoSeeds)
     }
 }
 
+/// Sorts `ranges` and joins any that overlap or touch. Empty ranges and
+/// values past `u64::MAX` are dropped. A run covering every value comes back
+/// as `(0, u64::MAX)` and `(u64::MAX, 1)`, as its length doesn't fit a `u64`.
+fn merge_ranges(ranges: Vec<ValueRange>) -> Vec<ValueRange> {
+    let mut spans: Vec<(u128, u128)> = ranges
+        .into_iter()
+        .filter(|r| r.1 > 0)
+        .map(|(start, length)| {
+            let end = (start as u128 + length as u128).min(u64::MAX as u128 + 1);
+            (start as u128, end)
+        })
+        .collect();
+    spans.sort_unstable();
+    spans
+        .into_iter()
+        .fold(vec![], |mut merged: Vec<(u128, u128)>, (start, end)| {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+            merged
+        })
+        .into_iter()
+        .flat_map(|(start, end)| match end - start > u64::MAX as u128 {
+            true => vec![(0, u64::MAX), (u64::MAX, 1)],
+            false => vec![(start as u64, (end - start) as u64)],
+        })
+        .collect()
+}
+
 impl DestinationSourceMapVec {
+    /// Maps every value in `ranges`, splitting each range wherever it crosses
+    /// an interval boundary. The output covers the same number of values.
+    fn lookup_ranges(&self, ranges: &[ValueRange]) -> Vec<ValueRange> {
+        let mut mapped = vec![];
+        ranges.iter().for_each(|&(mut start, mut remaining)| {
+            while remaining > 0 {
+                let after = self.0.partition_point(|i| i.source_start <= start);
+                let (dest, run) = match after.checked_sub(1).map(|idx| self.0[idx]) {
+                    Some(interval) if interval.contains(start) => {
+                        let offset = start - interval.source_start;
+                        (interval.dest_start + offset, interval.length - offset)
+                    }
+                    _ => match self.0.get(after) {
+                        Some(next) => (start, next.source_start - start),
+                        None => (start, remaining),
+                    },
+                };
+                let run = run.min(remaining);
+                mapped.push((dest, run));
+                remaining -= run;
+                if remaining > 0 {
+                    start += run;
+                }
+            }
+        });
+        mapped
+    }
+
+    /// Inclusive `(source_low, source_high, dest_low)` pieces covering every
+    /// `u64`, with gaps as identity pieces. Each value falls in the piece
+    /// `lookup_source` would use for it.
+    fn pieces(&self) -> Vec<(u64, u64, u64)> {
+        let mut pieces = vec![];
+        let mut cursor = Some(0u64);
+        for (idx, interval) in self.0.iter().enumerate() {
+            let Some(low) = cursor else { break };
+            if interval.length == 0 {
+                continue;
+            }
+            if low < interval.source_start {
+                pieces.push((low, interval.source_start - 1, low));
+            }
+            let low = low.max(interval.source_start);
+            let mut high = interval.source_start.saturating_add(interval.length - 1);
+            if let Some(next) = self.0.get(idx + 1) {
+                if next.source_start > interval.source_start {
+                    high = high.min(next.source_start - 1);
+                }
+            }
+            if low <= high {
+                let dest = interval.dest_start + (low - interval.source_start);
+                pieces.push((low, high, dest));
+                cursor = high.checked_add(1);
+            }
+        }
+        if let Some(low) = cursor {
+            pieces.push((low, u64::MAX, low));
+        }
+        pieces
+    }
+
+    /// Every source range whose values map into `ranges`, merged. Several
+    /// sources can share a destination, so this is the full preimage rather
+    /// than a strict inverse.
+    fn preimage_ranges(&self, ranges: &[ValueRange]) -> Vec<ValueRange> {
+        let pieces = self.pieces();
+        let mut sources = vec![];
+        for &(start, length) in ranges.iter().filter(|r| r.1 > 0) {
+            let high = start.saturating_add(length - 1);
+            for &(source_low, source_high, dest_low) in pieces.iter() {
+                let dest_high = dest_low.saturating_add(source_high - source_low);
+                let (low, top) = (start.max(dest_low), high.min(dest_high));
+                if low <= top {
+                    sources.push((source_low + (low - dest_low), top - low + 1));
+                }
+            }
+        }
+        merge_ranges(sources)
+    }
+
+    /// The single map equal to applying `self` and then `next`. Values that
+    /// end up where they started are left as gaps, and runs that line up
+    /// are joined into one interval.
+    fn then(&self, next: &DestinationSourceMapVec) -> DestinationSourceMapVec {
+        let next_pieces = next.pieces();
+        let mut intervals: Vec<Interval> = vec![];
+        for (source_low, source_high, dest_low) in self.pieces() {
+            let dest_high = dest_low.saturating_add(source_high - source_low);
+            let first = next_pieces.partition_point(|p| p.1 < dest_low);
+            for &(next_low, next_high, next_dest) in
+                next_pieces[first..].iter().take_while(|p| p.0 <= dest_high)
+            {
+                let (low, high) = (dest_low.max(next_low), dest_high.min(next_high));
+                let source_start = source_low + (low - dest_low);
+                let dest_start = next_dest + (low - next_low);
+                if source_start == dest_start {
+                    continue;
+                }
+                let length = high - low + 1;
+                match intervals.last_mut() {
+                    Some(last)
+                        if last.source_start + last.length == source_start
+                            && last.dest_start.checked_add(last.length) == Some(dest_start) =>
+                    {
+                        last.length += length
+                    }
+                    _ => intervals.push(Interval {
+                        source_start,
+                        dest_start,
+                        length,
+                    }),
+                }
+            }
+        }
+        DestinationSourceMapVec(intervals)
+    }
+
+    /// Source ranges where `self` and `other` send values to different
+    /// places, merged.
+    fn differences(&self, other: &DestinationSourceMapVec) -> Vec<ValueRange> {
+        let (ours, theirs) = (self.pieces(), other.pieces());
+        let (mut i, mut j) = (0, 0);
+        let mut differ = vec![];
+        while i < ours.len() && j < theirs.len() {
+            let (a, b) = (ours[i], theirs[j]);
+            let (low, high) = (a.0.max(b.0), a.1.min(b.1));
+            if a.2 + (low - a.0) != b.2 + (low - b.0) {
+                // Lengths saturate so a difference over every `u64` still fits.
+                differ.push((low, (high - low).saturating_add(1)));
+            }
+            match a.1.cmp(&b.1) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => (i, j) = (i + 1, j + 1),
+            }
+        }
+        merge_ranges(differ)
+    }
+
     fn lookup_source(&self, source: u64) -> u64 {
-        if let Some(map) = self.0.iter().find(|m| m.source == source) {
-            map.destination
-        } else {
-            source
+        self.lookup_entry(source).0
+    }
+
+    /// The value `source` maps to and the interval that mapped it, or `None`
+    /// when it passes through unchanged.
+    fn lookup_entry(&self, source: u64) -> (u64, Option<Interval>) {
+        let after = self.0.partition_point(|i| i.source_start <= source);
+        match after.checked_sub(1).map(|idx| self.0[idx]) {
+            Some(interval) if interval.contains(source) => (
+                interval.dest_start + (source - interval.source_start),
+                Some(interval),
+            ),
+            _ => (source, None),
         }
     }
 }
@@ -188,25 +967,69 @@ impl DestinationSourceMapVec {
 #[cfg(test)]
 mod tests {
 
-    use crate::{try_map_from_str, AlmanacMap, AlmanacRanges, DestinationSourceMapVec, EntireMap};
+    use crate::{
+        merge_ranges, try_map_from_str, validate_almanac, AlmanacError, AlmanacIssue,
+        AlmanacIssueKind, AlmanacMap, DestinationSourceMapVec, EntireMap, Interval, TraceStep,
+    };
+
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+            seed-to-soil map:
+            50 98 2
+            52 50 48
+
+            soil-to-fertilizer map:
+            0 15 37
+            37 52 2
+            39 0 15
+
+            fertilizer-to-water map:
+            49 53 8
+            0 11 42
+            42 0 7
+            57 7 4
+
+            water-to-light map:
+            88 18 7
+            18 25 70
+
+            light-to-temperature map:
+            45 77 23
+            81 45 19
+            68 64 13
+
+            temperature-to-humidity map:
+            0 69 1
+            1 0 69
+
+            humidity-to-location map:
+            60 56 37
+            56 93 4";
 
     #[test]
     fn example_seed_to_soil_map() {
         let input = "50 98 2\n52 50 48";
-        let maps: Vec<AlmanacMap> = input
-            .lines()
-            .into_iter()
-            .filter_map(|l| try_map_from_str(l))
-            .collect();
-        let ranges: Vec<AlmanacRanges> = maps.into_iter().map(|m| AlmanacRanges::from(m)).collect();
-        assert_eq!(vec![50, 51], ranges[0].destination_range);
-        assert_eq!(vec![98, 99], ranges[0].source_range);
+        let maps: Vec<AlmanacMap> = input.lines().filter_map(try_map_from_str).collect();
+        assert_eq!(vec![(50, 98, 2), (52, 50, 48)], maps);
 
-        let source_map = DestinationSourceMapVec::try_from(ranges.clone()).unwrap();
+        let source_map = DestinationSourceMapVec::from(maps);
         assert_eq!(
-            (50, 98),
-            (source_map.0[0].destination, source_map.0[0].source)
+            vec![
+                Interval {
+                    source_start: 50,
+                    dest_start: 52,
+                    length: 48
+                },
+                Interval {
+                    source_start: 98,
+                    dest_start: 50,
+                    length: 2
+                },
+            ],
+            source_map.0
         );
+        assert_eq!((98, 50), (98, source_map.lookup_source(98)));
+        assert_eq!((100, 100), (100, source_map.lookup_source(100)));
 
         let seeds_input = "79 14 55 13";
         let seeds: Vec<u64> = seeds_input
@@ -267,8 +1090,418 @@ mod tests {
 
         // let input = std::fs::read_to_string("src/input.txt").unwrap();
         let entire_map: EntireMap = EntireMap::from(input);
-        let seed_locs = entire_map.seed_locations();
+        let seed_locs = entire_map.seed_locations().unwrap();
         assert_eq!(vec![(82, 79), (43, 14), (86, 55), (35, 13)], seed_locs);
-        assert_eq!(35, entire_map.lowest_location());
+        assert_eq!(Ok(35), entire_map.lowest_location());
+    }
+
+    #[test]
+    fn lookup_at_interval_edges() {
+        let source_map =
+            DestinationSourceMapVec::from(vec![(0, u64::MAX - 1, 2), (100, 10, 5), (200, 15, 1)]);
+        assert_eq!(9, source_map.lookup_source(9));
+        assert_eq!(100, source_map.lookup_source(10));
+        assert_eq!(104, source_map.lookup_source(14));
+        assert_eq!(200, source_map.lookup_source(15));
+        assert_eq!(16, source_map.lookup_source(16));
+        assert_eq!(1, source_map.lookup_source(u64::MAX));
+    }
+
+    #[test]
+    fn lowest_location_for_committed_input() {
+        let input = std::fs::read_to_string("src/input.txt").unwrap();
+        let entire_map = EntireMap::from(input.as_str());
+        assert_eq!(Ok(218513636), entire_map.lowest_location());
+    }
+
+    #[test]
+    fn split_ranges_across_intervals() {
+        let source_map = DestinationSourceMapVec::from(vec![(50, 98, 2), (52, 50, 48)]);
+        assert_eq!(
+            vec![(40, 10), (52, 48), (50, 2), (100, 5)],
+            source_map.lookup_ranges(&[(40, 65)])
+        );
+        assert_eq!(vec![(60, 3)], source_map.lookup_ranges(&[(58, 3)]));
+        assert_eq!(
+            vec![(0, 10), (20, 5)],
+            merge_ranges(vec![(5, 5), (20, 5), (0, 6), (3, 0)])
+        );
+        assert_eq!(
+            vec![(0, u64::MAX), (u64::MAX, 1)],
+            merge_ranges(vec![(0, u64::MAX - 1), (u64::MAX - 1, 2)])
+        );
+        assert_eq!(
+            vec![(u64::MAX - 1, 2)],
+            merge_ranges(vec![(u64::MAX - 1, 5)])
+        );
+    }
+
+    #[test]
+    fn pt_two_example() {
+        let entire_map = EntireMap::from(EXAMPLE);
+        assert_eq!(Ok(vec![(79, 14), (55, 13)]), entire_map.seed_ranges());
+        let odd = EntireMap::from("seeds: 79 14 55\n\nseed-to-location map:\n0 0 10");
+        let malformed = Err(AlmanacError::Malformed("seeds: 79 14 55".to_string()));
+        assert_eq!(malformed, odd.lowest_range_location());
+        assert_eq!(malformed, odd.lowest_range_location_reverse());
+        assert_eq!(Ok(46), entire_map.lowest_range_location());
+
+        // Ranges ending at u64::MAX keep their last value.
+        let wrapped =
+            EntireMap::from("seeds: 5 3\n\nseed-to-location map:\n0 18446744073709551614 2");
+        assert_eq!(Ok(5), wrapped.lowest_range_location());
+        assert_eq!(Ok(5), wrapped.lowest_range_location_reverse());
+        let whole = EntireMap::from(
+            "seeds: 0 18446744073709551615 18446744073709551615 1\n\n\
+             seed-to-location map:\n1 0 18446744073709551615\n0 18446744073709551615 1",
+        );
+        assert_eq!(Ok(0), whole.lowest_range_location());
+        assert_eq!(Ok(0), whole.lowest_range_location_reverse());
+    }
+
+    #[test]
+    fn route_between_any_categories() {
+        let entire_map = EntireMap::from(EXAMPLE);
+        assert_eq!(Ok(74), entire_map.convert(79, "seed", "light"));
+        assert_eq!(Ok(78), entire_map.convert(81, "water", "humidity"));
+        assert_eq!(Ok(81), entire_map.convert(81, "soil", "soil"));
+        assert_eq!(
+            Err(AlmanacError::MissingLink {
+                from: "location".to_string(),
+                to: "seed".to_string()
+            }),
+            entire_map.convert(82, "location", "seed")
+        );
+
+        // Sections reordered, plus a shortcut map from seed straight to water.
+        let reordered = "seeds: 79 14 55 13
+
+            humidity-to-location map:
+            60 56 37
+            56 93 4
+
+            seed-to-soil map:
+            50 98 2
+            52 50 48
+
+            seed-to-water map:
+            0 0 200
+
+            water-to-humidity map:
+            0 0 1";
+        let entire_map = EntireMap::from(reordered);
+        assert_eq!(Ok(81), entire_map.convert(79, "seed", "soil"));
+        assert_eq!(Ok(79), entire_map.convert(79, "seed", "water"));
+        assert_eq!(
+            vec![(83, 79), (14, 14), (55, 55), (13, 13)],
+            entire_map.seed_locations().unwrap()
+        );
+
+        let looped = "seeds: 1
+
+            seed-to-soil map:
+            0 0 1
+
+            soil-to-water map:
+            0 0 1
+
+            water-to-soil map:
+            0 0 1";
+        assert_eq!(
+            Err(AlmanacError::Cycle(vec![
+                "soil".to_string(),
+                "water".to_string(),
+                "soil".to_string()
+            ])),
+            EntireMap::parse(looped).map(|_| ())
+        );
+        assert!(matches!(
+            EntireMap::parse("seeds: 1\n\nsoil map:\n1 2 3"),
+            Err(AlmanacError::Malformed(_))
+        ));
+        assert_eq!(
+            Err(AlmanacError::Overflow(
+                "18446744073709551614 0 10".to_string()
+            )),
+            EntireMap::parse("seeds: 5 3\n\nseed-to-location map:\n18446744073709551614 0 10")
+                .map(|_| ())
+        );
+        assert_eq!(
+            Err(AlmanacError::Overflow(
+                "0 18446744073709551615 2".to_string()
+            )),
+            EntireMap::parse("seeds: 5 3\n\nseed-to-location map:\n0 18446744073709551615 2")
+                .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn lowest_location_reports_almanac_errors() {
+        let missing = AlmanacError::MissingLink {
+            from: "seed".to_string(),
+            to: "location".to_string(),
+        };
+        let no_location = EntireMap::from("seeds: 79 14\n\nseed-to-soil map:\n50 98 2");
+        assert_eq!(Err(missing.clone()), no_location.lowest_location());
+        assert_eq!(Err(missing.clone()), no_location.lowest_range_location());
+        assert_eq!(Err(missing), no_location.lowest_range_location_reverse());
+
+        let no_seeds = EntireMap::from("seeds:\n\nseed-to-location map:\n50 98 2");
+        assert_eq!(Err(AlmanacError::NoSeeds), no_seeds.lowest_location());
+        assert_eq!(Err(AlmanacError::NoSeeds), no_seeds.lowest_range_location());
+        assert_eq!(
+            Err(AlmanacError::NoSeeds),
+            no_seeds.lowest_range_location_reverse()
+        );
+    }
+
+    #[test]
+    fn preimage_of_map_and_chain() {
+        let source_map = DestinationSourceMapVec::from(vec![(50, 98, 2), (52, 50, 48)]);
+        assert_eq!(vec![(98, 2)], source_map.preimage_ranges(&[(50, 2)]));
+        assert_eq!(vec![(10, 1)], source_map.preimage_ranges(&[(10, 1)]));
+        assert_eq!(
+            vec![(97, 1), (100, 2)],
+            source_map.preimage_ranges(&[(99, 3)])
+        );
+
+        // Two sources land on 5: the interval start and the identity gap.
+        let folded = DestinationSourceMapVec::from(vec![(5, 0, 1)]);
+        assert_eq!(vec![(0, 1), (5, 1)], folded.preimage_ranges(&[(5, 1)]));
+        assert_eq!(Vec::<(u64, u64)>::new(), folded.preimage_ranges(&[(0, 1)]));
+
+        let entire_map = EntireMap::from(EXAMPLE);
+        for (location, seed) in entire_map.seed_locations().unwrap() {
+            let seeds = entire_map.preimage(&[(location, 1)], "seed", "location");
+            assert!(seeds
+                .unwrap()
+                .iter()
+                .any(|&(start, length)| start <= seed && seed - start < length));
+        }
+        assert_eq!(Ok(46), entire_map.lowest_range_location_reverse());
+
+        let input = std::fs::read_to_string("src/input.txt").unwrap();
+        let entire_map = EntireMap::from(input.as_str());
+        assert_eq!(
+            entire_map.lowest_range_location(),
+            entire_map.lowest_range_location_reverse()
+        );
+    }
+
+    #[test]
+    fn flattened_chain_matches_stepwise_lookup() {
+        let first = DestinationSourceMapVec::from(vec![(50, 98, 2), (52, 50, 48)]);
+        let second = DestinationSourceMapVec::from(vec![(0, 15, 37), (37, 52, 2), (39, 0, 15)]);
+        let composed = first.then(&second);
+        (0..120).for_each(|v| {
+            assert_eq!(
+                second.lookup_source(first.lookup_source(v)),
+                composed.lookup_source(v)
+            )
+        });
+        assert_eq!(
+            vec![
+                (39, 0, 15),
+                (0, 15, 35),
+                (37, 50, 2),
+                (54, 52, 46),
+                (35, 98, 2)
+            ],
+            composed
+                .0
+                .iter()
+                .map(|i| (i.dest_start, i.source_start, i.length))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("39 0 15\n0 15 35\n37 50 2\n", &composed.to_string()[..24]);
+
+        let entire_map = EntireMap::from(EXAMPLE);
+        let flat = entire_map.flatten("seed", "location").unwrap();
+        (0..120).for_each(|v| {
+            assert_eq!(
+                entire_map.convert(v, "seed", "location"),
+                Ok(flat.lookup_source(v))
+            )
+        });
+        assert_eq!(
+            Ok(DestinationSourceMapVec(vec![])),
+            entire_map.flatten("soil", "soil")
+        );
+
+        assert!(flat.differences(&flat).is_empty());
+        let nudged = flat.then(&DestinationSourceMapVec::from(vec![(1000, 35, 1)]));
+        assert_eq!(vec![(13, 1)], flat.differences(&nudged));
+        let identity = DestinationSourceMapVec(vec![]);
+        assert_eq!(
+            vec![(0, 100)],
+            DestinationSourceMapVec::from(vec![(1, 0, 100)]).differences(&identity)
+        );
+    }
+
+    #[test]
+    fn validate_almanac_entries() {
+        assert!(validate_almanac(EXAMPLE).is_empty());
+        let input = std::fs::read_to_string("src/input.txt").unwrap();
+        assert!(validate_almanac(&input).is_empty());
+
+        let input = "seeds: 1 2
+
+seed-to-soil map:
+50 98 2
+52 50 49
+0 0 0
+1 2
+
+soil-to-water map:
+0 18446744073709551615 2
+0 10 5
+3 30 1";
+        let issue = |line, map: &str, kind| AlmanacIssue {
+            line,
+            map: map.to_string(),
+            kind,
+        };
+        assert_eq!(
+            vec![
+                issue(4, "seed-to-soil", AlmanacIssueKind::OverlappingSource(5)),
+                issue(6, "seed-to-soil", AlmanacIssueKind::ZeroLength),
+                issue(7, "seed-to-soil", AlmanacIssueKind::MalformedEntry),
+                issue(10, "soil-to-water", AlmanacIssueKind::SourceOverflow),
+                issue(
+                    11,
+                    "soil-to-water",
+                    AlmanacIssueKind::OverlappingDestination(10)
+                ),
+                issue(
+                    12,
+                    "soil-to-water",
+                    AlmanacIssueKind::OverlappingDestination(11)
+                ),
+            ],
+            validate_almanac(input)
+        );
+        assert_eq!(
+            "line 5 (seed-to-soil): source range overlaps line 4",
+            issue(5, "seed-to-soil", AlmanacIssueKind::OverlappingSource(4)).to_string()
+        );
+    }
+
+    #[test]
+    fn trace_seed_through_every_stage() {
+        let entire_map = EntireMap::from(EXAMPLE);
+        let trace = entire_map.trace(79).unwrap();
+        let values: Vec<(&str, u64)> = trace
+            .steps
+            .iter()
+            .map(|s| (s.category.as_str(), s.value))
+            .collect();
+        assert_eq!(
+            vec![
+                ("soil", 81),
+                ("fertilizer", 81),
+                ("water", 81),
+                ("light", 74),
+                ("temperature", 78),
+                ("humidity", 78),
+                ("location", 82)
+            ],
+            values
+        );
+        assert_eq!(
+            TraceStep {
+                category: "soil".to_string(),
+                value: 81,
+                entry: Some((5, (52, 50, 48)))
+            },
+            trace.steps[0]
+        );
+        assert_eq!(None, trace.steps[1].entry);
+        assert!(trace
+            .to_string()
+            .starts_with("seed 79\n  soil 81 (line 5: 52 50 48)\n  fertilizer 81 (identity)\n"));
+
+        let trace = entire_map.trace(13).unwrap();
+        assert!(trace.to_json().starts_with(
+            "{\"seed\":13,\"steps\":[{\"category\":\"soil\",\"value\":13,\"entry\":null},\
+             {\"category\":\"fertilizer\",\"value\":52,\
+             \"entry\":{\"line\":10,\"dest\":39,\"source\":0,\"length\":15}}"
+        ));
+        assert_eq!(35, trace.steps.last().unwrap().value);
+
+        let quoted = EntireMap::from(
+            "seeds: 5\n\nseed-to-\"s\\oil\" map:\n0 0 10\n\n\"s\\oil\"-to-location map:",
+        );
+        assert_eq!(
+            "{\"seed\":5,\"steps\":[{\"category\":\"\\\"s\\\\oil\\\"\",\"value\":5,\
+             \"entry\":{\"line\":4,\"dest\":0,\"source\":0,\"length\":10}},\
+             {\"category\":\"location\",\"value\":5,\"entry\":null}]}",
+            quoted.trace(5).unwrap().to_json()
+        );
+
+        // An entry trimmed by an earlier overlap is reported as listed.
+        let overlapping =
+            EntireMap::from("seeds: 40\n\nseed-to-location map:\n5 10 20\n1000 0 100");
+        let step = &overlapping.trace(40).unwrap().steps[0];
+        assert_eq!((1040, Some((5, (1000, 0, 100)))), (step.value, step.entry));
+    }
+
+    #[test]
+    fn export_csv_and_dot() {
+        let entire_map = EntireMap::from(EXAMPLE);
+        let csv = entire_map.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(19, lines.len());
+        assert_eq!("from,to,dest_start,source_start,length", lines[0]);
+        assert_eq!("seed,soil,52,50,48", lines[1]);
+        assert_eq!("seed,soil,50,98,2", lines[2]);
+        assert_eq!("humidity,location,56,93,4", lines[18]);
+
+        let dot = entire_map.to_dot();
+        assert!(dot.starts_with("digraph almanac {\n    \"seed\";\n    \"soil\";\n"));
+        assert!(dot.contains("    \"seed\" -> \"soil\" [label=\"2 intervals\"];\n"));
+        assert!(dot.contains("    \"fertilizer\" -> \"water\" [label=\"4 intervals\"];\n"));
+        assert_eq!(8, dot.lines().filter(|l| l.ends_with("\";")).count());
+        assert_eq!(7, dot.matches(" -> ").count());
+        assert!(dot.ends_with("}\n"));
+
+        let single = EntireMap::from("seeds: 1\n\nseed-to-soil map:\n1 2 3");
+        assert!(single
+            .to_dot()
+            .contains("\"seed\" -> \"soil\" [label=\"1 interval\"];"));
+    }
+
+    #[test]
+    fn overlapping_entries_resolve_to_the_first_listed() {
+        let source_map = DestinationSourceMapVec::from(vec![(1000, 0, 100), (2000, 10, 5)]);
+        assert_eq!(1050, source_map.lookup_source(50));
+        assert_eq!(1012, source_map.lookup_source(12));
+        assert_eq!(100, source_map.lookup_source(100));
+
+        // Listed the other way round, the shorter entry wins where they meet.
+        let source_map = DestinationSourceMapVec::from(vec![(2000, 10, 5), (1000, 0, 100)]);
+        assert_eq!(1009, source_map.lookup_source(9));
+        assert_eq!(2002, source_map.lookup_source(12));
+        assert_eq!(1050, source_map.lookup_source(50));
+        assert_eq!(
+            vec![(1000, 0, 10), (2000, 10, 5), (1015, 15, 85)],
+            source_map
+                .0
+                .iter()
+                .map(|i| (i.dest_start, i.source_start, i.length))
+                .collect::<Vec<_>>()
+        );
+
+        let same_start = DestinationSourceMapVec::from(vec![(500, 7, 1), (900, 7, 3), (0, 8, 0)]);
+        assert_eq!(500, same_start.lookup_source(7));
+        assert_eq!(901, same_start.lookup_source(8));
+        assert_eq!(
+            vec![(7, 1), (500, 1)],
+            same_start.preimage_ranges(&[(500, 1)])
+        );
+        // 900 itself comes from the identity gap, not the shadowed entry.
+        assert_eq!(
+            vec![(8, 2), (900, 3)],
+            same_start.preimage_ranges(&[(900, 3)])
+        );
     }
 }
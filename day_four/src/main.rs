@@ -1,9 +1,80 @@
-use std::fs;
+use std::{collections::HashSet, env, fs, thread};
 
 fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let mode = match args.iter().position(|a| a == "--strict") {
+        Some(i) => {
+            args.remove(i);
+            ValidationMode::Strict
+        }
+        None => ValidationMode::Lenient,
+    };
+    if args.len() < 2 {
+        println!(
+            "Usage: cargo run -- <pt1 [doubling | linear | fibonacci | <rule file>], pt2, check \
+             or explain [<card> [tree | json] [depth]]> [--strict]"
+        );
+        return;
+    }
+
     let input = fs::read_to_string("src/input.txt").unwrap();
-    let cards = CardPile::from(input.as_str());
-    println!("{}", cards.sum());
+    let cards = match CardPile::parse(&input, mode) {
+        Ok((cards, warnings)) => {
+            warnings.iter().for_each(|w| eprintln!("warning: {}", w));
+            cards
+        }
+        Err(errors) => {
+            errors.iter().for_each(|e| println!("error: {}", e));
+            return;
+        }
+    };
+    match args[1].as_str() {
+        "pt1" => {
+            let rule = match args.get(2).map(|a| a.as_str()) {
+                None => Ok(ScoringRule::Doubling),
+                Some(name @ ("doubling" | "linear" | "fibonacci")) => name.parse(),
+                Some(path) => fs::read_to_string(path)
+                    .map_err(|e| format!("{}: {}", path, e))
+                    .and_then(|config| config.parse()),
+            };
+            match rule.map(|rule| cards.total_score(&rule)) {
+                Ok(Ok(total)) => println!("{}", total),
+                Ok(Err(e)) => println!("{}", e),
+                Err(e) => println!("{}", e),
+            }
+        }
+        "pt2" => match cards.total_cards() {
+            Ok(total) => println!("{}", total),
+            Err(e) => println!("{}", e),
+        },
+        "check" => match cards.validate_numbering() {
+            Ok(()) => println!("{} cards, numbered 1 to {}", cards.0.len(), cards.0.len()),
+            Err(report) => print!("{}", report),
+        },
+        "explain" if args.len() == 2 => match cards.cascade_table() {
+            Ok(table) => print!("{}", table),
+            Err(e) => println!("{}", e),
+        },
+        "explain" => {
+            let Some(pos) = args[2]
+                .parse()
+                .ok()
+                .and_then(|id: u32| cards.0.iter().position(|c| c.id == id))
+            else {
+                return println!("No card {} in the pile", args[2]);
+            };
+            let depth = args.get(4).and_then(|d| d.parse().ok()).unwrap_or(3);
+            match (
+                cards.cascade_tree(pos, depth),
+                args.get(3).map(|a| a.as_str()),
+            ) {
+                (Ok(tree), Some("json")) => println!("{}", tree.to_json()),
+                (Ok(tree), _) => print!("{}", tree),
+                (Err(e), _) => println!("{}", e),
+            }
+        }
+        _ => println!("Invalid argument. Use 'pt1', 'pt2', 'check' or 'explain'."),
+    }
 }
 
 #[derive(Debug)]
@@ -11,96 +82,653 @@ pub struct CardPile(Vec<ScratchCard>);
 
 #[derive(Debug)]
 pub struct ScratchCard {
-    winning_nums: Vec<u32>,
+    id: u32,
+    /// 1-based input line the card was read from; 1 for a lone card.
+    line: usize,
     given_nums: Vec<(u32, bool)>,
 }
 
-impl From<&str> for CardPile {
-    fn from(value: &str) -> Self {
-        Self(
-            value
-                .lines()
-                .into_iter()
-                .filter_map(|l| {
-                    if !l.trim().is_empty() {
-                        Some(ScratchCard::from(l))
-                    } else {
-                        None
-                    }
+/// A card's winning numbers, for constant-time membership checks. Small
+/// numbers go in a bitset; a card with sparse huge numbers falls back to a
+/// sorted list searched by bisection.
+#[derive(Debug)]
+enum NumberSet {
+    Bits(Vec<u64>),
+    Sorted(Vec<u32>),
+}
+
+/// Deepest `CardPile::cascade_tree` will go. Each level can multiply the
+/// tree by the number of spawning cards, so deeper requests are clamped.
+const MAX_CASCADE_DEPTH: usize = 6;
+
+/// Piles with fewer cards than this are parsed on the calling thread.
+const PARALLEL_PARSE_THRESHOLD: usize = 4096;
+
+/// How many points a card is worth for its number of matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScoringRule {
+    /// 1 point for the first match, doubled for each one after.
+    Doubling,
+    /// 1 point per match.
+    Linear,
+    /// 1, 2, 3, 5, 8, ... points for 1, 2, 3, 4, 5, ... matches.
+    Fibonacci,
+    /// Points for 0, 1, 2, ... matches; more matches than entries is an error.
+    Table(Vec<u64>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScoreError {
+    /// The card's (or the pile's running) score does not fit in a `u64`.
+    Overflow { card: u32 },
+    /// A `ScoringRule::Table` has no entry for this many matches.
+    NoTableEntry { card: u32, matches: usize },
+}
+
+/// A card's instances, or the running total of instances up to it, do not
+/// fit in a `u64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyOverflow {
+    card: u32,
+}
+
+/// Where a card's instances came from under the copy cascade: `copies` of it
+/// were won from each card in `spawned_by`, itself explained recursively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CascadeNode {
+    id: u32,
+    instances: u64,
+    /// Copies of this card won from the spawning card, or every copy won for
+    /// the root.
+    copies: u64,
+    spawned_by: Vec<CascadeNode>,
+    /// Set when `spawned_by` was cut off by the depth limit.
+    truncated: bool,
+}
+
+/// Whether `CardPile::parse` fails on card issues or only warns about them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    Strict,
+    /// Issues are returned as warnings and malformed lines are skipped.
+    Lenient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardIssueKind {
+    /// The line is missing its `:` or `|` separator.
+    Malformed,
+    /// The text before `:` is not `Card` followed by a `u32`.
+    BadCardId(String),
+    DuplicateWinning(u32),
+    DuplicateGiven(u32),
+    NonNumeric(String),
+    /// `(winning, given)` counts differ from the first card's.
+    InconsistentCounts {
+        found: (usize, usize),
+        expected: (usize, usize),
+    },
+}
+
+/// A problem with the card on 1-based input line `line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardIssue {
+    line: usize,
+    kind: CardIssueKind,
+}
+
+/// Everything wrong with a pile's `Card N` numbering, which should run
+/// 1, 2, 3, ... in order.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NumberingReport {
+    /// Inclusive ranges of ids between 1 and the highest id that no card has.
+    gaps: Vec<(u32, u32)>,
+    /// Ids from 1 up used by more than one card.
+    duplicates: Vec<u32>,
+    /// Input lines of cards numbered 0, which is never a valid id.
+    zero: Vec<usize>,
+    /// `(input line, id)` for cards numbered lower than the card before.
+    out_of_order: Vec<(usize, u32)>,
+}
+
+impl std::fmt::Display for NumberingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |items: Vec<String>| items.join(", ");
+        if !self.gaps.is_empty() {
+            let gaps = self
+                .gaps
+                .iter()
+                .map(|(first, last)| match first == last {
+                    true => first.to_string(),
+                    false => format!("{}-{}", first, last),
                 })
-                .collect(),
+                .collect();
+            writeln!(f, "missing cards: {}", join(gaps))?;
+        }
+        if !self.duplicates.is_empty() {
+            let duplicates = self.duplicates.iter().map(|id| id.to_string()).collect();
+            writeln!(f, "duplicated cards: {}", join(duplicates))?;
+        }
+        if !self.zero.is_empty() {
+            let lines = self.zero.iter().map(|l| format!("line {}", l)).collect();
+            writeln!(f, "cards numbered 0: {}", join(lines))?;
+        }
+        if !self.out_of_order.is_empty() {
+            let out_of_order = self
+                .out_of_order
+                .iter()
+                .map(|(line, id)| format!("card {} at line {}", id, line))
+                .collect();
+            writeln!(f, "out of order: {}", join(out_of_order))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overflow { card } => write!(f, "card {}: score overflows u64", card),
+            Self::NoTableEntry { card, matches } => write!(
+                f,
+                "card {}: scoring table has no entry for {} matches",
+                card, matches
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScoreError {}
+
+impl std::fmt::Display for CopyOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "card {}: instance count overflows u64", self.card)
+    }
+}
+
+impl std::error::Error for CopyOverflow {}
+
+/// Rule config: `doubling`, `linear`, `fibonacci`, or `table` followed by the
+/// points for 0, 1, 2, ... matches, separated by whitespace. Lines starting
+/// with `#` are comments.
+impl std::str::FromStr for ScoringRule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .flat_map(|l| l.split_whitespace());
+        let rule = match words.next() {
+            Some("doubling") => Self::Doubling,
+            Some("linear") => Self::Linear,
+            Some("fibonacci") => Self::Fibonacci,
+            Some("table") => {
+                let points: Vec<u64> = words
+                    .by_ref()
+                    .map(|w| w.parse().map_err(|_| format!("{} is not a point value", w)))
+                    .collect::<Result<_, _>>()?;
+                if points.is_empty() {
+                    return Err("table needs at least one point value".into());
+                }
+                Self::Table(points)
+            }
+            Some(other) => return Err(format!("unknown scoring rule {}", other)),
+            None => return Err("empty scoring rule".into()),
+        };
+        match words.next() {
+            Some(extra) => Err(format!("unexpected {} after scoring rule", extra)),
+            None => Ok(rule),
+        }
+    }
+}
+
+impl ScoringRule {
+    /// Points for `matches` winning numbers, `None` if it overflows a `u64`
+    /// or a table has no entry for it.
+    fn points(&self, matches: usize) -> Option<u64> {
+        match self {
+            Self::Doubling => match matches {
+                0 => Some(0),
+                n => 1u64.checked_shl(u32::try_from(n - 1).ok()?),
+            },
+            Self::Linear => u64::try_from(matches).ok(),
+            Self::Fibonacci => (0..matches)
+                .try_fold((0u64, 1u64), |(a, b), _| Some((b, a.checked_add(b)?)))
+                .map(|(_, b)| if matches == 0 { 0 } else { b }),
+            Self::Table(points) => points.get(matches).copied(),
+        }
+    }
+}
+
+impl std::fmt::Display for CardIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            CardIssueKind::Malformed => write!(f, "expected 'Card N: <winning> | <given>'"),
+            CardIssueKind::BadCardId(header) => write!(f, "expected 'Card N', found {:?}", header),
+            CardIssueKind::DuplicateWinning(n) => write!(f, "duplicate winning number {}", n),
+            CardIssueKind::DuplicateGiven(n) => write!(f, "duplicate given number {}", n),
+            CardIssueKind::NonNumeric(token) => write!(f, "non-numeric token {:?}", token),
+            CardIssueKind::InconsistentCounts { found, expected } => write!(
+                f,
+                "{} winning and {} given numbers, expected {} and {}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+/// Checks each card line for what `ScratchCard::from` would panic on or
+/// silently get wrong: missing separators, a bad `Card N` header, repeated
+/// numbers on either side, tokens that are not `u32`s, and counts differing
+/// from the first card.
+fn validate_cards(input: &str) -> Vec<CardIssue> {
+    let mut issues = vec![];
+    let mut expected: Option<(usize, usize)> = None;
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .for_each(|(i, l)| {
+            let line = i + 1;
+            let Some((winning_str, given_str)) =
+                l.split_once(':').and_then(|(_, nums)| nums.split_once('|'))
+            else {
+                issues.push(CardIssue {
+                    line,
+                    kind: CardIssueKind::Malformed,
+                });
+                return;
+            };
+            let header = l.split_once(':').map_or("", |(h, _)| h.trim());
+            let id = header
+                .strip_prefix("Card")
+                .and_then(|id| id.trim().parse::<u32>().ok());
+            if id.is_none() {
+                issues.push(CardIssue {
+                    line,
+                    kind: CardIssueKind::BadCardId(header.to_string()),
+                });
+            }
+            // Reports issues on one side of the `|`, returning how many numbers it has.
+            let mut side = |nums: &str, duplicate: fn(u32) -> CardIssueKind| {
+                let mut seen: HashSet<u32> = HashSet::new();
+                let mut reported: HashSet<u32> = HashSet::new();
+                let mut count = 0;
+                nums.split_whitespace()
+                    .for_each(|token| match token.parse() {
+                        Ok(n) => {
+                            count += 1;
+                            if !seen.insert(n) && reported.insert(n) {
+                                issues.push(CardIssue {
+                                    line,
+                                    kind: duplicate(n),
+                                });
+                            }
+                        }
+                        Err(_) => issues.push(CardIssue {
+                            line,
+                            kind: CardIssueKind::NonNumeric(token.to_string()),
+                        }),
+                    });
+                count
+            };
+            let found = (
+                side(winning_str, CardIssueKind::DuplicateWinning),
+                side(given_str, CardIssueKind::DuplicateGiven),
+            );
+            match expected {
+                None => expected = Some(found),
+                Some(expected) if expected != found => issues.push(CardIssue {
+                    line,
+                    kind: CardIssueKind::InconsistentCounts { found, expected },
+                }),
+                Some(_) => {}
+            }
+        });
+    issues
+}
+
+impl CascadeNode {
+    pub fn to_json(&self) -> String {
+        let spawned_by: Vec<String> = self.spawned_by.iter().map(|n| n.to_json()).collect();
+        format!(
+            "{{\"card\":{},\"instances\":{},\"copies\":{},\"truncated\":{},\"spawned_by\":[{}]}}",
+            self.id,
+            self.instances,
+            self.copies,
+            self.truncated,
+            spawned_by.join(",")
         )
     }
+
+    fn write_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}card {} ({} instances) gave {} copies{}",
+            "  ".repeat(depth),
+            self.id,
+            self.instances,
+            self.copies,
+            if self.truncated { " ..." } else { "" }
+        )?;
+        self.spawned_by
+            .iter()
+            .try_for_each(|n| n.write_indented(f, depth + 1))
+    }
+}
+
+/// The root card's instances, then each spawning card indented below the
+/// card it gave copies to.
+impl std::fmt::Display for CascadeNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "card {}: {} instances (1 original, {} copies)",
+            self.id,
+            self.instances,
+            self.instances - 1
+        )?;
+        self.spawned_by
+            .iter()
+            .try_for_each(|n| n.write_indented(f, 1))
+    }
+}
+
+impl From<&[u32]> for NumberSet {
+    fn from(nums: &[u32]) -> Self {
+        let max = nums.iter().copied().max().unwrap_or(0) as usize;
+        // Keep the bitset within a few words per number.
+        if max / 64 <= 4 * nums.len() + 16 {
+            let mut bits = vec![0u64; max / 64 + 1];
+            nums.iter()
+                .for_each(|n| bits[*n as usize / 64] |= 1 << (n % 64));
+            Self::Bits(bits)
+        } else {
+            let mut sorted = nums.to_vec();
+            sorted.sort_unstable();
+            Self::Sorted(sorted)
+        }
+    }
+}
+
+impl NumberSet {
+    fn contains(&self, n: u32) -> bool {
+        match self {
+            Self::Bits(bits) => bits
+                .get(n as usize / 64)
+                .is_some_and(|word| word & (1 << (n % 64)) != 0),
+            Self::Sorted(sorted) => sorted.binary_search(&n).is_ok(),
+        }
+    }
+}
+
+/// Parses each `(line number, text)` into a card, splitting large piles
+/// across threads.
+fn parse_cards(lines: &[(usize, &str)]) -> Vec<ScratchCard> {
+    let read = |&(line, text): &(usize, &str)| ScratchCard {
+        line,
+        ..ScratchCard::from(text)
+    };
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    if lines.len() < PARALLEL_PARSE_THRESHOLD || threads == 1 {
+        return lines.iter().map(read).collect();
+    }
+    let chunk_size = lines.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let handles: Vec<_> = lines
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(read).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("Card parsing thread panicked"))
+            .collect()
+    })
+}
+
+impl From<&str> for CardPile {
+    fn from(value: &str) -> Self {
+        let lines: Vec<(usize, &str)> = value
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(i, l)| (i + 1, l))
+            .collect();
+        Self(parse_cards(&lines))
+    }
 }
 
 impl From<&str> for ScratchCard {
     fn from(value: &str) -> Self {
-        let (winning_str, given_str) = value
+        let (id_str, numbers_str) = value
             .split_once(':')
-            .expect(&format!("Couldn't split {} on ':'", value))
-            .1
+            .unwrap_or_else(|| panic!("Couldn't split {} on ':'", value));
+        let id: u32 = id_str
+            .trim()
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .unwrap_or_else(|| panic!("Couldn't get card id from {}", id_str));
+        let (winning_str, given_str) = numbers_str
             .split_once('|')
-            .expect(&format!("Couldn't split {} on '|'", value));
+            .unwrap_or_else(|| panic!("Couldn't split {} on '|'", value));
         let winning_nums: Vec<u32> = winning_str
-            .split(' ')
-            .into_iter()
+            .split_whitespace()
             .filter_map(|s| s.parse().ok())
             .collect();
+        let winning_set = NumberSet::from(winning_nums.as_slice());
         let given_nums = given_str
-            .split(' ')
-            .into_iter()
-            .filter_map(|s| {
-                if let Some(num) = s.parse().ok() {
-                    if winning_nums.iter().any(|n| n == &num) {
-                        Some((num, true))
-                    } else {
-                        Some((num, false))
-                    }
-                } else {
-                    None
-                }
-            })
+            .split_whitespace()
+            .filter_map(|s| s.parse().ok())
+            .map(|num| (num, winning_set.contains(num)))
             .collect();
         Self {
-            winning_nums,
+            id,
+            line: 1,
             given_nums,
         }
     }
 }
 
 impl CardPile {
-    fn sum(&self) -> u64 {
-        self.0.iter().fold(0, |mut sum, c| {
-            sum += c.score();
-            sum
+    /// Validates `input` with `validate_cards` before parsing it. Strict mode
+    /// fails with every issue; lenient mode returns them as warnings alongside
+    /// a pile built from the well-formed lines.
+    fn parse(input: &str, mode: ValidationMode) -> Result<(Self, Vec<CardIssue>), Vec<CardIssue>> {
+        let issues = validate_cards(input);
+        if mode == ValidationMode::Strict && !issues.is_empty() {
+            return Err(issues);
+        }
+        let malformed: HashSet<usize> = issues
+            .iter()
+            .filter(|i| {
+                matches!(
+                    i.kind,
+                    CardIssueKind::Malformed | CardIssueKind::BadCardId(_)
+                )
+            })
+            .map(|i| i.line)
+            .collect();
+        let lines: Vec<(usize, &str)> = input
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l))
+            .filter(|(line, l)| !l.trim().is_empty() && !malformed.contains(line))
+            .collect();
+        Ok((Self(parse_cards(&lines)), issues))
+    }
+
+    /// Sum of every card's points under `rule`, failing on the first card
+    /// that cannot be scored or when the total overflows.
+    fn total_score(&self, rule: &ScoringRule) -> Result<u64, ScoreError> {
+        self.0.iter().try_fold(0u64, |sum, c| {
+            sum.checked_add(c.score_with(rule)?)
+                .ok_or(ScoreError::Overflow { card: c.id })
         })
     }
+
+    /// Checks that cards are numbered 1 to N with no gaps or repeats, in order.
+    fn validate_numbering(&self) -> Result<(), NumberingReport> {
+        let mut report = NumberingReport::default();
+        let mut ids: Vec<u32> = self.0.iter().map(|c| c.id).filter(|id| *id > 0).collect();
+        ids.sort_unstable();
+        let mut last = 0;
+        ids.chunk_by(|a, b| a == b).for_each(|run| {
+            let id = run[0];
+            if id > last + 1 {
+                report.gaps.push((last + 1, id - 1));
+            }
+            if run.len() > 1 {
+                report.duplicates.push(id);
+            }
+            last = id;
+        });
+        report.zero = self
+            .0
+            .iter()
+            .filter(|c| c.id == 0)
+            .map(|c| c.line)
+            .collect();
+        self.0.windows(2).for_each(|pair| {
+            if pair[1].id < pair[0].id {
+                report.out_of_order.push((pair[1].line, pair[1].id));
+            }
+        });
+        match report == NumberingReport::default() {
+            true => Ok(()),
+            false => Err(report),
+        }
+    }
+
+    /// Each card wins one copy of each of the next `matches` cards, and every
+    /// copy wins again. Returns how many cards, originals and copies, are
+    /// processed in total.
+    fn total_cards(&self) -> Result<u64, CopyOverflow> {
+        self.instances()?
+            .iter()
+            .zip(&self.0)
+            .try_fold(0u64, |total, (instances, card)| {
+                total
+                    .checked_add(*instances)
+                    .ok_or(CopyOverflow { card: card.id })
+            })
+    }
+
+    /// Instances of each card in the pile, original included, under the copy
+    /// cascade. Each card's winnings are recorded as a range in a difference
+    /// array, so this is linear in the number of cards. Counts grow
+    /// exponentially, so this fails at the first card that overflows a `u64`.
+    fn instances(&self) -> Result<Vec<u64>, CopyOverflow> {
+        let len = self.0.len();
+        // Each entry sums at most `len` counts that fit in a `u64`, so it
+        // cannot overflow an `i128`.
+        let mut diff = vec![0i128; len + 1];
+        let mut copies = Vec::with_capacity(len);
+        let mut won = 0i128;
+        for (i, card) in self.0.iter().enumerate() {
+            won += diff[i];
+            let instances = u64::try_from(won + 1).map_err(|_| CopyOverflow { card: card.id })?;
+            copies.push(instances);
+            let end = (i + 1 + card.matches()).min(len);
+            if i + 1 < end {
+                diff[i + 1] += instances as i128;
+                diff[end] -= instances as i128;
+            }
+        }
+        Ok(copies)
+    }
+
+    /// Positions of the cards that win copies of the card at `pos`.
+    fn spawners(&self, pos: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..pos).filter(move |i| i + self.0[*i].matches() >= pos)
+    }
+
+    /// Explains the card at `pos`, following spawning cards `depth` levels up,
+    /// at most `MAX_CASCADE_DEPTH`.
+    fn cascade_tree(&self, pos: usize, depth: usize) -> Result<CascadeNode, CopyOverflow> {
+        let instances = self.instances()?;
+        let depth = depth.min(MAX_CASCADE_DEPTH);
+        Ok(self.cascade_node(pos, instances[pos] - 1, depth, &instances))
+    }
+
+    fn cascade_node(
+        &self,
+        pos: usize,
+        copies: u64,
+        depth: usize,
+        instances: &[u64],
+    ) -> CascadeNode {
+        let spawned_by = match depth {
+            0 => vec![],
+            _ => self
+                .spawners(pos)
+                .map(|i| self.cascade_node(i, instances[i], depth - 1, instances))
+                .collect(),
+        };
+        CascadeNode {
+            id: self.0[pos].id,
+            instances: instances[pos],
+            copies,
+            truncated: depth == 0 && self.spawners(pos).next().is_some(),
+            spawned_by,
+        }
+    }
+
+    /// Per-card table of matches, original, copies won and total instances.
+    fn cascade_table(&self) -> Result<String, CopyOverflow> {
+        let mut out = format!(
+            "{:>5} | {:>7} | {:>8} | {:>10} | {:>9}\n",
+            "card", "matches", "original", "copies won", "instances"
+        );
+        out.push_str(&format!("{}\n", "-".repeat(51)));
+        self.0
+            .iter()
+            .zip(self.instances()?)
+            .for_each(|(card, instances)| {
+                out.push_str(&format!(
+                    "{:>5} | {:>7} | {:>8} | {:>10} | {:>9}\n",
+                    card.id,
+                    card.matches(),
+                    1,
+                    instances - 1,
+                    instances
+                ));
+            });
+        out.push_str(&format!("total {}\n", self.total_cards()?));
+        Ok(out)
+    }
 }
 
 impl ScratchCard {
-    fn score(&self) -> u64 {
-        self.given_nums.iter().fold(0, |mut score, (n, b)| {
-            match *b {
-                true => {
-                    println!("{} IS A WINNER", n);
-                    println!("SCORE BEFORE: {}", score);
-                    if score == 0 {
-                        score += 1;
-                    } else {
-                        score = score * 2;
-                    }
-                    println!("SCORE AFTER: {}", score);
-                }
-                false => score = score,
-            }
-            score
+    fn matches(&self) -> usize {
+        self.given_nums.iter().filter(|(_, won)| *won).count()
+    }
+
+    fn score_with(&self, rule: &ScoringRule) -> Result<u64, ScoreError> {
+        let matches = self.matches();
+        rule.points(matches).ok_or(match rule {
+            ScoringRule::Table(points) if matches >= points.len() => ScoreError::NoTableEntry {
+                card: self.id,
+                matches,
+            },
+            _ => ScoreError::Overflow { card: self.id },
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::CardPile;
+    use crate::{
+        parse_cards, CardIssue, CardIssueKind, CardPile, CascadeNode, CopyOverflow, NumberSet,
+        NumberingReport, ScoreError, ScoringRule, ValidationMode, MAX_CASCADE_DEPTH,
+    };
+
+    const EXAMPLE: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+            Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+            Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+            Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+            Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+            Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
     #[test]
     fn solve_part_one_example() {
@@ -112,12 +740,328 @@ mod tests {
             Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
         let cards: CardPile = CardPile::from(input);
         println!("{:?}", cards);
-        assert_eq!(8, cards.0[0].score());
-        assert_eq!(2, cards.0[1].score());
-        assert_eq!(2, cards.0[2].score());
-        assert_eq!(1, cards.0[3].score());
-        assert_eq!(0, cards.0[4].score());
-        assert_eq!(0, cards.0[5].score());
-        assert_eq!(13, cards.sum());
+        let score = |i: usize| cards.0[i].score_with(&ScoringRule::Doubling);
+        assert_eq!(Ok(8), score(0));
+        assert_eq!(Ok(2), score(1));
+        assert_eq!(Ok(2), score(2));
+        assert_eq!(Ok(1), score(3));
+        assert_eq!(Ok(0), score(4));
+        assert_eq!(Ok(0), score(5));
+        assert_eq!(Ok(13), cards.total_score(&ScoringRule::Doubling));
+    }
+
+    #[test]
+    fn solve_part_two_example() {
+        let cards: CardPile = CardPile::from(EXAMPLE);
+        assert_eq!(Ok(30), cards.total_cards());
+    }
+
+    #[test]
+    fn card_ids_and_numbering() {
+        let input = "Card 1: 41 48 | 83 86\n
+            Card   2: 13 32 | 61 30\n
+            Card 4: 1 21 | 69 82\n
+            Card 3: 41 92 | 59 84\n
+            Card 4: 87 83 | 88 30\n
+            Card 7: 31 18 | 74 77";
+        let cards = CardPile::from(input);
+        let ids: Vec<u32> = cards.0.iter().map(|c| c.id).collect();
+        assert_eq!(vec![1, 2, 4, 3, 4, 7], ids);
+
+        let report = cards.validate_numbering().unwrap_err();
+        assert_eq!(
+            NumberingReport {
+                gaps: vec![(5, 6)],
+                duplicates: vec![4],
+                zero: vec![],
+                out_of_order: vec![(7, 3)],
+            },
+            report
+        );
+        assert_eq!(
+            "missing cards: 5-6\nduplicated cards: 4\nout of order: card 3 at line 7\n",
+            report.to_string()
+        );
+
+        let cards = CardPile::from("Card 0: 1 | 2\nCard 2: 1 | 2\nCard 4000000000: 1 | 2");
+        let report = cards.validate_numbering().unwrap_err();
+        assert_eq!(
+            NumberingReport {
+                gaps: vec![(1, 1), (3, 3_999_999_999)],
+                duplicates: vec![],
+                zero: vec![1],
+                out_of_order: vec![],
+            },
+            report
+        );
+        assert_eq!(
+            "missing cards: 1, 3-3999999999\ncards numbered 0: line 1\n",
+            report.to_string()
+        );
+
+        let input = std::fs::read_to_string("src/input.txt").unwrap();
+        assert!(CardPile::from(input.as_str()).validate_numbering().is_ok());
+    }
+
+    #[test]
+    fn scoring_rules() {
+        let cards = CardPile::from(EXAMPLE);
+        assert_eq!(Ok(13), cards.total_score(&ScoringRule::Doubling));
+        assert_eq!(Ok(9), cards.total_score(&ScoringRule::Linear));
+        assert_eq!(Ok(10), cards.total_score(&ScoringRule::Fibonacci));
+
+        let rule: ScoringRule = "# squares\ntable 0 1 4\n 9 16".parse().unwrap();
+        assert_eq!(ScoringRule::Table(vec![0, 1, 4, 9, 16]), rule);
+        assert_eq!(Ok(25), cards.total_score(&rule));
+        assert_eq!(
+            Err(ScoreError::NoTableEntry {
+                card: 1,
+                matches: 4
+            }),
+            cards.total_score(&"table 0 1 2".parse().unwrap())
+        );
+        assert!("table".parse::<ScoringRule>().is_err());
+        assert!("linear 3".parse::<ScoringRule>().is_err());
+
+        assert_eq!(Some(1 << 63), ScoringRule::Doubling.points(64));
+        assert_eq!(None, ScoringRule::Doubling.points(65));
+        assert_eq!(
+            Some(12200160415121876738),
+            ScoringRule::Fibonacci.points(92)
+        );
+        assert_eq!(None, ScoringRule::Fibonacci.points(93));
+
+        let big = format!("Card 1: {} | {}", "7 ".repeat(70), "7 ".repeat(70));
+        let big = CardPile::from(big.as_str());
+        assert_eq!(
+            Err(ScoreError::Overflow { card: 1 }),
+            big.total_score(&ScoringRule::Doubling)
+        );
+    }
+
+    #[test]
+    fn validation_strict_and_lenient() {
+        let input = "Card 1: 41 48 83 | 83 86  6 31\n\
+            Card 2: 13 32 13 | 61 30 61 61\n\
+            Card 3:  1 x1 53 | 69 82 63 72\n\
+            Card 4 41 92 73 | 59 84 76 51\n\
+            Card 5: 87 83 | 88 30 70 12 93";
+        let issue = |line, kind| CardIssue { line, kind };
+        let expected = vec![
+            issue(2, CardIssueKind::DuplicateWinning(13)),
+            issue(2, CardIssueKind::DuplicateGiven(61)),
+            issue(3, CardIssueKind::NonNumeric("x1".to_string())),
+            issue(
+                3,
+                CardIssueKind::InconsistentCounts {
+                    found: (2, 4),
+                    expected: (3, 4),
+                },
+            ),
+            issue(4, CardIssueKind::Malformed),
+            issue(
+                5,
+                CardIssueKind::InconsistentCounts {
+                    found: (2, 5),
+                    expected: (3, 4),
+                },
+            ),
+        ];
+        assert_eq!(
+            Err(expected.clone()),
+            CardPile::parse(input, ValidationMode::Strict).map(|(_, w)| w)
+        );
+
+        let (cards, warnings) = CardPile::parse(input, ValidationMode::Lenient).unwrap();
+        assert_eq!(expected, warnings);
+        let ids: Vec<u32> = cards.0.iter().map(|c| c.id).collect();
+        assert_eq!(vec![1, 2, 3, 5], ids);
+        assert_eq!("line 3: non-numeric token \"x1\"", warnings[2].to_string());
+
+        let input = "Card 1: 1 2 | 3 4\nCard x: 1 2 | 3 4\nCrad 3: 1 2 | 3 4";
+        let expected = vec![
+            issue(2, CardIssueKind::BadCardId("Card x".to_string())),
+            issue(3, CardIssueKind::BadCardId("Crad 3".to_string())),
+        ];
+        assert_eq!(
+            Err(expected.clone()),
+            CardPile::parse(input, ValidationMode::Strict).map(|(_, w)| w)
+        );
+        let (cards, warnings) = CardPile::parse(input, ValidationMode::Lenient).unwrap();
+        assert_eq!(expected, warnings);
+        assert_eq!(1, cards.0.len());
+        assert_eq!(
+            "line 2: expected 'Card N', found \"Card x\"",
+            warnings[0].to_string()
+        );
+
+        let (cards, warnings) =
+            CardPile::parse("Card 1: 1\t2 3 | 1\t2 3", ValidationMode::Strict).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(3, cards.0[0].matches());
+
+        let input = std::fs::read_to_string("src/input.txt").unwrap();
+        assert!(CardPile::parse(&input, ValidationMode::Strict).is_ok());
+    }
+
+    #[test]
+    fn explain_copy_cascade() {
+        let cards = CardPile::from(EXAMPLE);
+        assert_eq!(Ok(vec![1, 2, 4, 8, 14, 1]), cards.instances());
+
+        let leaf = |id, instances| CascadeNode {
+            id,
+            instances,
+            copies: instances,
+            spawned_by: vec![],
+            truncated: false,
+        };
+        let tree = cards.cascade_tree(2, 2).unwrap();
+        assert_eq!(
+            CascadeNode {
+                id: 3,
+                instances: 4,
+                copies: 3,
+                spawned_by: vec![
+                    leaf(1, 1),
+                    CascadeNode {
+                        spawned_by: vec![leaf(1, 1)],
+                        ..leaf(2, 2)
+                    },
+                ],
+                truncated: false,
+            },
+            tree
+        );
+        assert_eq!(
+            "card 3: 4 instances (1 original, 3 copies)\n  card 1 (1 instances) gave 1 copies\n  card 2 (2 instances) gave 2 copies\n    card 1 (1 instances) gave 1 copies\n",
+            tree.to_string()
+        );
+        let truncated = cards.cascade_tree(2, 1).unwrap();
+        assert!(truncated.spawned_by[1].truncated);
+        assert!(!truncated.spawned_by[0].truncated);
+        assert_eq!(
+            "{\"card\":2,\"instances\":2,\"copies\":2,\"truncated\":true,\"spawned_by\":[]}",
+            truncated.spawned_by[1].to_json()
+        );
+
+        let chain: String = (1..=40)
+            .map(|id| format!("Card {}: 1 2 | 1 2\n", id))
+            .collect();
+        let chain = CardPile::from(chain.as_str());
+        fn depth(node: &CascadeNode) -> usize {
+            node.spawned_by
+                .iter()
+                .map(|n| depth(n) + 1)
+                .max()
+                .unwrap_or(0)
+        }
+        let tree = chain.cascade_tree(39, 100).unwrap();
+        assert_eq!(MAX_CASCADE_DEPTH, depth(&tree));
+        let deepest = (0..MAX_CASCADE_DEPTH).fold(&tree, |node, _| &node.spawned_by[0]);
+        assert!(deepest.truncated);
+
+        let table = cards.cascade_table().unwrap();
+        assert!(table.contains("    5 |       0 |        1 |         13 |        14\n"));
+        assert!(table.ends_with("total 30\n"));
+    }
+
+    #[test]
+    fn set_matching_on_large_generated_piles() {
+        // Small xorshift so the generated pile is the same on every run.
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        let mut lines = vec![];
+        for id in 1..=5000 {
+            let bound = if id % 100 == 0 { u32::MAX as u64 } else { 1000 };
+            let winning: Vec<String> = (0..50).map(|_| next(bound).to_string()).collect();
+            let given: Vec<String> = (0..100).map(|_| next(bound).to_string()).collect();
+            lines.push(format!(
+                "Card {}: {} | {}",
+                id,
+                winning.join(" "),
+                given.join(" ")
+            ));
+        }
+        let lines: Vec<(usize, &str)> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.as_str()))
+            .collect();
+        let cards = parse_cards(&lines);
+        assert_eq!(5000, cards.len());
+        cards
+            .iter()
+            .zip(&lines)
+            .for_each(|(card, &(line_num, line))| {
+                assert_eq!(line_num, card.line);
+                let (winning, given) = line.split_once(':').unwrap().1.split_once('|').unwrap();
+                let winning: Vec<u32> = winning
+                    .split_whitespace()
+                    .map(|n| n.parse().unwrap())
+                    .collect();
+                let naive = given
+                    .split_whitespace()
+                    .map(|n| n.parse().unwrap())
+                    .filter(|n| winning.contains(n))
+                    .count();
+                assert_eq!(naive, card.matches(), "card {}", card.id);
+            });
+        assert!(matches!(
+            NumberSet::from([3, 64, 999].as_slice()),
+            NumberSet::Bits(_)
+        ));
+        let sparse = NumberSet::from([7, 4_000_000_000].as_slice());
+        assert!(matches!(sparse, NumberSet::Sorted(_)));
+        assert!(sparse.contains(4_000_000_000) && !sparse.contains(8));
+
+        // Naive cascade in u128, capped so counts past u64 stay put.
+        let naive_instances = |pile: &CardPile| {
+            let mut naive = vec![1u128; pile.0.len()];
+            (0..pile.0.len()).for_each(|i| {
+                let won = naive[i];
+                naive
+                    .iter_mut()
+                    .skip(i + 1)
+                    .take(pile.0[i].matches())
+                    .for_each(|c| *c = (*c + won).min(u64::MAX as u128 + 1));
+            });
+            match naive.iter().position(|c| *c > u64::MAX as u128) {
+                Some(pos) => Err(CopyOverflow {
+                    card: pile.0[pos].id,
+                }),
+                None => Ok(naive.into_iter().map(|c| c as u64).collect()),
+            }
+        };
+        let pile = CardPile(cards);
+        assert_eq!(naive_instances(&pile), pile.instances());
+        let short = CardPile(pile.0.into_iter().take(40).collect());
+        assert!(short.instances().is_ok());
+        assert_eq!(naive_instances(&short), short.instances());
+    }
+
+    #[test]
+    fn copy_counts_overflow_with_an_error() {
+        let pile = |cards: u32| {
+            let input: String = (1..=cards)
+                .map(|id| format!("Card {}: 1 2 3 | 1 2 3\n", id))
+                .collect();
+            CardPile::from(input.as_str())
+        };
+        // Instances grow like a tribonacci sequence, passing u64::MAX at card 74.
+        assert_eq!(Err(CopyOverflow { card: 74 }), pile(200).total_cards());
+        assert_eq!(Err(CopyOverflow { card: 74 }), pile(200).cascade_table());
+        assert!(pile(73).instances().is_ok());
+        assert_eq!(Err(CopyOverflow { card: 73 }), pile(73).total_cards());
+        assert_eq!(
+            "card 73: instance count overflows u64",
+            pile(73).total_cards().unwrap_err().to_string()
+        );
+        assert!(pile(72).total_cards().is_ok());
     }
 }
@@ -1,24 +1,291 @@
-use std::{char, fs};
+use std::{
+    char,
+    collections::{HashMap, HashSet},
+    env, fs,
+    time::{Duration, Instant},
+};
+
+const USAGE: &str =
+    "Usage: cargo run -- <pt1, pt2, render [html <path>], clusters [dot | json], bench [size] \
+     or query <symbol <char> | at <row> <col> | number <n>>> \
+     [--neighborhood <4 | 8 | rN>] [--reach <char>=<4 | 8 | rN>]... [--pad-short-rows]";
 
 fn main() {
+    let (mut args, rules) = match parse_rules(env::args().collect()) {
+        Ok(parsed) => parsed,
+        Err(e) => return println!("{}\n{}", e, USAGE),
+    };
+    let ragged = match args.iter().position(|a| a == "--pad-short-rows") {
+        Some(i) => {
+            args.remove(i);
+            RaggedRows::Pad
+        }
+        None => RaggedRows::Reject,
+    };
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if args[1] == "bench" {
+        let size = args.get(2).and_then(|a| a.parse().ok()).unwrap_or(1000);
+        return bench(size, rules);
+    }
+
     let input = fs::read_to_string("src/input.txt").unwrap();
 
-    let grid = Grid::from_input_string(&input);
+    let grid = match Grid::parse(&input, rules, ragged) {
+        Ok(grid) => grid,
+        Err(e) => return println!("{}", e),
+    };
 
     // println!("{:?}", grid);
-    println!("{}", grid.get_sum_of_all_part_numbers());
+    match args[1].as_str() {
+        "pt1" => println!("{}", grid.get_sum_of_all_part_numbers()),
+        "pt2" => println!("{}", grid.get_sum_of_gear_ratios()),
+        "query" => {
+            let index = grid.adjacency_index();
+            let args: Vec<&str> = args[2..].iter().map(|a| a.as_str()).collect();
+            let results = match args.as_slice() {
+                ["symbol", c] if c.chars().count() == 1 => {
+                    index.by_symbol(c.chars().next().unwrap())
+                }
+                ["at", row, col] => match (row.parse(), col.parse()) {
+                    (Ok(row), Ok(col)) => index.at((row, col)).into_iter().collect(),
+                    _ => return println!("{}", USAGE),
+                },
+                ["number", n] => match n.parse() {
+                    Ok(n) => index.by_number(n),
+                    _ => return println!("{}", USAGE),
+                },
+                _ => return println!("{}", USAGE),
+            };
+            results.into_iter().for_each(|(spot, touching)| {
+                let touching: Vec<String> = touching.into_iter().map(|t| t.to_string()).collect();
+                println!("{} -> [{}]", spot, touching.join(", "))
+            });
+        }
+        "render" => match args.get(2).map(|a| a.as_str()) {
+            Some("html") => {
+                let path = args.get(3).map(|a| a.as_str()).unwrap_or("schematic.html");
+                fs::write(path, grid.render_html()).unwrap();
+                println!("Wrote {}", path);
+            }
+            _ => print!("{}", grid.render_ansi()),
+        },
+        "clusters" => {
+            let index = grid.adjacency_index();
+            match args.get(2).map(|a| a.as_str()) {
+                Some("dot") => print!("{}", index.to_dot()),
+                Some("json") => println!("{}", index.to_json()),
+                _ => {
+                    let clusters = index.clusters();
+                    println!("{} clusters", clusters.len());
+                    if let Some(largest) = clusters.iter().max_by_key(|c| c.len()) {
+                        println!(
+                            "largest: {} numbers, {} symbols, total {}",
+                            largest.numbers.len(),
+                            largest.symbols.len(),
+                            largest.total()
+                        );
+                        largest
+                            .symbols
+                            .iter()
+                            .chain(&largest.numbers)
+                            .for_each(|spot| println!("  {}", spot));
+                    }
+                }
+            }
+        }
+        _ => println!(
+            "Invalid argument. Use 'pt1', 'pt2', 'render', 'clusters', 'bench' or 'query'."
+        ),
+    }
+}
+
+/// A random `size` x `size` schematic: mostly `.`, with runs of digits and
+/// scattered symbols. The same `seed` always gives the same schematic.
+fn generate_schematic(size: usize, seed: u64) -> String {
+    let mut state = seed | 1;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let symbols = ['*', '#', '+', '$', '/', '&', '@', '=', '-', '%'];
+    let mut out = String::with_capacity(size * (size + 1));
+    for _ in 0..size {
+        for _ in 0..size {
+            let roll = next() % 100;
+            out.push(match roll {
+                0..=69 => '.',
+                70..=91 => char::from(b'0' + (next() % 10) as u8),
+                _ => symbols[(next() % symbols.len() as u64) as usize],
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Times parsing and part detection on generated schematics of doubling
+/// size. Time per cell should stay roughly flat if both scale linearly.
+fn bench(size: usize, rules: AdjacencyRules) {
+    println!(
+        "{:>7} | {:>12} | {:>10} | {:>8}",
+        "size", "cells", "total", "ns/cell"
+    );
+    for size in [size / 4, size / 2, size].into_iter().filter(|s| *s > 0) {
+        let input = generate_schematic(size, 0x5EED);
+        let start = Instant::now();
+        let grid = Grid::from_input_string_with_rules(&input, rules.clone());
+        let sum = grid.get_sum_of_all_part_numbers();
+        let elapsed: Duration = start.elapsed();
+        let cells = size * size;
+        println!(
+            "{:>7} | {:>12} | {:>10.2?} | {:>8.1}   (sum {})",
+            size,
+            cells,
+            elapsed,
+            elapsed.as_nanos() as f64 / cells as f64,
+            sum
+        );
+    }
+}
+
+/// Pulls `--neighborhood` and `--reach` flags out of `args`, returning the
+/// remaining arguments and the adjacency rules they describe.
+fn parse_rules(args: Vec<String>) -> Result<(Vec<String>, AdjacencyRules), String> {
+    let mut rules = AdjacencyRules::default();
+    let mut rest = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--neighborhood" => {
+                let value = args.next().ok_or("--neighborhood needs a value")?;
+                rules.default = value.parse()?;
+            }
+            "--reach" => {
+                let value = args.next().ok_or("--reach needs a value")?;
+                let (symbol, neighborhood) = value
+                    .split_once('=')
+                    .ok_or(format!("{} is not <char>=<neighborhood>", value))?;
+                let mut chars = symbol.chars();
+                let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                    return Err(format!("{} is not a single character", symbol));
+                };
+                rules.per_symbol.insert(symbol, neighborhood.parse()?);
+            }
+            _ => rest.push(arg),
+        }
+    }
+    Ok((rest, rules))
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct GridSpot {
     value: GridValue,
     positions: Vec<(usize, usize)>,
-    adjacencies: Vec<(usize, usize)>,
     is_part: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct Grid(Vec<GridSpot>);
+struct Grid {
+    /// Every number and symbol, in reading order.
+    spots: Vec<GridSpot>,
+    /// Decides which spots touch.
+    rules: AdjacencyRules,
+    /// `(rows, cols)`, as wide as the widest row.
+    dims: (usize, usize),
+}
+
+/// Which cells around a spot count as touching it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Neighborhood {
+    /// Orthogonal neighbours only.
+    FourConnected,
+    /// Orthogonal and diagonal neighbours.
+    #[default]
+    EightConnected,
+    /// Every cell within `n` rows and `n` columns.
+    Chebyshev(usize),
+}
+
+/// The neighborhood used for every symbol, with optional overrides for
+/// specific symbol characters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct AdjacencyRules {
+    default: Neighborhood,
+    per_symbol: HashMap<char, Neighborhood>,
+}
+
+/// Lookup tables relating every number in a `Grid` to the symbols it touches
+/// and every symbol to the numbers it touches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AdjacencyIndex<'a> {
+    grid: &'a Grid,
+    /// Spot index occupying each cell, row-major, `EMPTY_CELL` if none.
+    cells: Vec<u32>,
+    /// For each spot index, the indices of adjacent spots of the other kind,
+    /// in grid order.
+    neighbours: Vec<Vec<usize>>,
+}
+
+/// A connected component of the number/symbol adjacency graph with at least
+/// one edge, spots in grid order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cluster<'a> {
+    numbers: Vec<&'a GridSpot>,
+    symbols: Vec<&'a GridSpot>,
+}
+
+/// What to do with rows shorter than the widest row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RaggedRows {
+    #[default]
+    Reject,
+    /// Accept short rows, treating the missing cells as `.`. The grid is as
+    /// wide as the widest row either way.
+    Pad,
+}
+
+/// A problem found by `validate_schematic`, at a 1-based line and column of
+/// the input as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SchematicIssue {
+    /// `len` and `expected` count cells, ignoring surrounding whitespace.
+    RaggedRow {
+        line: usize,
+        len: usize,
+        expected: usize,
+    },
+    NonPrintable {
+        line: usize,
+        col: usize,
+        c: char,
+    },
+    UnexpectedToken {
+        line: usize,
+        col: usize,
+        token: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SchematicError(Vec<SchematicIssue>);
+
+/// How a rendered cell should be highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+    Empty,
+    Part,
+    NonPart,
+    Symbol,
+    Gear,
+}
+
+const EMPTY_CELL: u32 = u32::MAX;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum GridValue {
@@ -29,7 +296,7 @@ enum GridValue {
 impl TryFrom<&str> for GridValue {
     type Error = Box<dyn std::error::Error>;
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if let Some(number) = value.parse::<u64>().ok() {
+        if let Ok(number) = value.parse::<u64>() {
             return Ok(Self::Number(number));
         }
         match value.len() == 1 {
@@ -43,232 +310,702 @@ impl TryFrom<&str> for GridValue {
     }
 }
 
-impl Into<String> for GridValue {
-    fn into(self) -> String {
-        match self {
-            Self::Number(num) => format!("{}", num),
-            Self::Character(char) => format!("{}", char),
+impl From<GridValue> for String {
+    fn from(value: GridValue) -> Self {
+        match value {
+            GridValue::Number(num) => format!("{}", num),
+            GridValue::Character(char) => format!("{}", char),
         }
     }
 }
 
-impl GridValue {
-    fn inner_num(&self) -> Option<u64> {
-        match self {
-            Self::Number(num) => Some(*num),
-            _ => None,
-        }
+impl std::fmt::Display for GridSpot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value: String = self.value.clone().into();
+        let (row, col) = self.positions[0];
+        write!(f, "{} @ ({}, {})", value, row, col)
     }
-    fn is_character(&self) -> bool {
+}
+
+impl std::fmt::Display for SchematicIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            GridValue::Character(_) => true,
-            _ => false,
+            Self::RaggedRow {
+                line,
+                len,
+                expected,
+            } => write!(
+                f,
+                "line {}: {} columns wide, expected {}",
+                line, len, expected
+            ),
+            Self::NonPrintable { line, col, c } => write!(
+                f,
+                "line {}, col {}: non-printable character {:?}",
+                line, col, c
+            ),
+            Self::UnexpectedToken { line, col, token } => {
+                write!(
+                    f,
+                    "line {}, col {}: unexpected token {:?}",
+                    line, col, token
+                )
+            }
         }
     }
+}
 
-    fn is_number(&self) -> bool {
-        match self {
-            GridValue::Number(_) => true,
-            _ => false,
+impl std::fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Invalid schematic ({} issues):", self.0.len())?;
+        self.0
+            .iter()
+            .try_for_each(|issue| writeln!(f, "  {}", issue))
+    }
+}
+
+impl std::error::Error for SchematicError {}
+
+/// Checks every row for the problems `from_input_string_with_rules` would
+/// otherwise panic on or silently accept: rows narrower than the widest one,
+/// control characters, and tokens that are neither `.`, a `u64` number nor
+/// ASCII punctuation. Widths are compared after trimming, the same way the
+/// grid reads rows, but positions refer to the untrimmed input.
+fn validate_schematic(input: &str) -> Vec<SchematicIssue> {
+    // (line number, column of the first cell, cells)
+    let lines: Vec<(usize, usize, Vec<char>)> = input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            let indent = l.chars().count() - l.trim_start().chars().count();
+            (i + 1, indent + 1, l.trim().chars().collect())
+        })
+        .collect();
+    let width = lines.iter().map(|l| l.2.len()).max().unwrap_or(0);
+    let mut issues = vec![];
+    lines.iter().for_each(|(line, first_col, chars)| {
+        let line = *line;
+        let at = |idx: usize| first_col + idx;
+        if chars.len() != width {
+            issues.push(SchematicIssue::RaggedRow {
+                line,
+                len: chars.len(),
+                expected: width,
+            });
         }
+        let mut col = 0;
+        while col < chars.len() {
+            let start = col;
+            let c = chars[col];
+            col += 1;
+            if c.is_ascii_digit() {
+                while col < chars.len() && chars[col].is_ascii_digit() {
+                    col += 1;
+                }
+                let token: String = chars[start..col].iter().collect();
+                if token.parse::<u64>().is_err() {
+                    issues.push(SchematicIssue::UnexpectedToken {
+                        line,
+                        col: at(start),
+                        token,
+                    });
+                }
+            } else if c.is_control() {
+                issues.push(SchematicIssue::NonPrintable {
+                    line,
+                    col: at(start),
+                    c,
+                });
+            } else if !c.is_ascii_punctuation() {
+                issues.push(SchematicIssue::UnexpectedToken {
+                    line,
+                    col: at(start),
+                    token: c.to_string(),
+                });
+            }
+        }
+    });
+    issues
+}
+
+impl Cluster<'_> {
+    fn len(&self) -> usize {
+        self.numbers.len() + self.symbols.len()
+    }
+
+    fn total(&self) -> u64 {
+        self.numbers
+            .iter()
+            .filter_map(|n| n.value.inner_num())
+            .sum()
     }
 }
 
-impl Grid {
-    fn from_input_string(input: &str) -> Self {
-        let mut grid = Self(vec![]);
-        let lines: Vec<&str> = input
-            .lines()
-            .filter_map(|l| {
-                let trim = l.trim();
-                if !l.is_empty() {
-                    Some(trim)
-                } else {
-                    None
+impl<'a> AdjacencyIndex<'a> {
+    fn new(grid: &'a Grid) -> Self {
+        let (rows, cols) = grid.dims;
+        let mut cells = vec![EMPTY_CELL; rows * cols];
+        grid.spots.iter().enumerate().for_each(|(i, spot)| {
+            spot.positions
+                .iter()
+                .for_each(|(row, col)| cells[row * cols + col] = i as u32)
+        });
+        let cell = |(row, col): (usize, usize)| match cells[row * cols + col] {
+            EMPTY_CELL => None,
+            idx => Some(idx as usize),
+        };
+        let mut neighbours = vec![vec![]; grid.spots.len()];
+        grid.spots.iter().enumerate().for_each(|(sym_idx, spot)| {
+            let GridValue::Character(c) = spot.value else {
+                return;
+            };
+            let reach = grid.rules.for_symbol(c).cells(&spot.positions, rows, cols);
+            reach.into_iter().for_each(|adj| {
+                if let Some(num_idx) = cell(adj) {
+                    if grid.spots[num_idx].value.is_number() {
+                        neighbours[num_idx].push(sym_idx);
+                        neighbours[sym_idx].push(num_idx);
+                    }
                 }
             })
+        });
+        neighbours.iter_mut().for_each(|n| {
+            n.sort_unstable();
+            n.dedup();
+        });
+        Self {
+            grid,
+            cells,
+            neighbours,
+        }
+    }
+
+    fn with_neighbours(&self, idx: usize) -> (&'a GridSpot, Vec<&'a GridSpot>) {
+        let grid = self.grid;
+        let touching = self.neighbours[idx]
+            .iter()
+            .map(|i| &grid.spots[*i])
             .collect();
-        let len = lines.len() - 1;
-        lines
-            .into_iter()
-            .enumerate()
-            .for_each(|(i, l)| grid.0.append(&mut Self::from_line_info(l, i, i == len)));
-        grid.check_for_parts();
-        grid
+        (&grid.spots[idx], touching)
     }
 
-    fn from_line_info(line: &str, line_num: usize, last_line: bool) -> Vec<GridSpot> {
-        let spot_vals: Vec<GridValue> =
-            line.split('.')
-                .filter(|v| !v.trim().is_empty())
-                .fold(vec![], |mut acc, v| {
-                    if v.len() > 1 && v.contains(|c: char| c.is_ascii_punctuation()) {
-                        if let Some(idx) = v.find(|c: char| c.is_ascii_punctuation()) {
-                            let char = v.chars().nth(idx).unwrap();
-                            v.split(char)
-                                .into_iter()
-                                .filter(|k| !k.trim().is_empty())
-                                .for_each(|val| {
-                                    let spot_value =
-                                        GridValue::try_from(val).expect("Spot value get failure");
-                                    acc.push(spot_value);
-                                });
-                            let spot_value = GridValue::try_from(char.to_string().as_str())
-                                .expect("Spot value get failure");
-                            acc.push(spot_value);
-                        }
-                    } else {
-                        let spot_value = GridValue::try_from(v).expect("Spot value get failure");
-                        acc.push(spot_value);
+    /// Every occurrence of the symbol `c`, each with the numbers touching it.
+    fn by_symbol(&self, c: char) -> Vec<(&'a GridSpot, Vec<&'a GridSpot>)> {
+        self.matching(|v| *v == GridValue::Character(c))
+    }
+
+    /// Every occurrence of the number `n`, each with the symbols touching it.
+    fn by_number(&self, n: u64) -> Vec<(&'a GridSpot, Vec<&'a GridSpot>)> {
+        self.matching(|v| *v == GridValue::Number(n))
+    }
+
+    /// The spot covering `coord`, if any, with the spots of the other kind
+    /// touching it.
+    fn at(&self, coord: (usize, usize)) -> Option<(&'a GridSpot, Vec<&'a GridSpot>)> {
+        let (rows, cols) = self.grid.dims;
+        if coord.0 >= rows || coord.1 >= cols {
+            return None;
+        }
+        match self.cells[coord.0 * cols + coord.1] {
+            EMPTY_CELL => None,
+            idx => Some(self.with_neighbours(idx as usize)),
+        }
+    }
+
+    /// Spot indices of each connected component with at least one edge,
+    /// symbols first, each group in grid order.
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.neighbours.len()];
+        let mut components = vec![];
+        for start in 0..self.neighbours.len() {
+            if seen[start] || self.neighbours[start].is_empty() {
+                continue;
+            }
+            seen[start] = true;
+            let mut members = vec![start];
+            let mut stack = vec![start];
+            while let Some(idx) = stack.pop() {
+                self.neighbours[idx].iter().for_each(|&next| {
+                    if !seen[next] {
+                        seen[next] = true;
+                        members.push(next);
+                        stack.push(next);
                     }
-                    acc
-                });
-        println!("Got spot values: {:?}", spot_vals);
-        let spot_pos_val_tups: Vec<(Vec<(usize, usize)>, GridValue)> =
-            spot_vals.iter().fold(vec![], |mut tups, v| {
-                let pattern: String = v.clone().into();
-                println!(
-                    "PATTERN: {}\nLINE: {}\nMATCHES: {:?}\n",
-                    pattern,
-                    line,
-                    line.clone()
-                        .match_indices(&pattern)
-                        .collect::<Vec<(usize, &str)>>()
-                );
-                line.match_indices(&pattern).for_each(|(i, p)| {
-                    let position = p.chars().enumerate().fold(vec![], |mut acc, (k, _)| {
-                        acc.push((line_num, i + k));
-                        acc
-                    });
-                    tups.push((position, v.clone()));
                 });
-                tups
-            });
+            }
+            members.sort_unstable_by_key(|idx| (self.grid.spots[*idx].value.is_number(), *idx));
+            components.push(members);
+        }
+        components
+    }
 
-        println!("Got spot value pos tups: {:?}\n", spot_pos_val_tups);
-        spot_pos_val_tups
+    /// Connected components of the bipartite number/symbol graph, skipping
+    /// spots that touch nothing. Ordered by their first spot in the grid.
+    fn clusters(&self) -> Vec<Cluster<'a>> {
+        self.components()
             .into_iter()
-            .fold(vec![], |mut return_spots, (positions, value)| {
-                println!("Getting adjacencies for: {:?}", value);
-                let adjacencies = positions.clone().into_iter().fold(
-                    vec![],
-                    |mut adj_acc: Vec<(usize, usize)>, (line_idx, char_idx)| {
-                        let include_prev_line = line_idx != 0;
-                        let include_next_line = !last_line;
-                        let include_prev_char =
-                            !positions.iter().any(|p| p.1 < char_idx) && char_idx != 0;
-                        let include_next_char =
-                            !positions.iter().any(|p| p.1 > char_idx) && char_idx != line.len() - 1;
-                        println!("BEFORE PASS {:?}\n", adj_acc);
-                        println!(
-                            "COORDS: {:?}\nPREV LINE: {}\nNEXT LINE: {}\nPREV CHAR: {}\nNEXT CHAR: {}\n",
-                            (line_idx, char_idx),
-                            include_prev_line,
-                            include_next_line,
-                            include_prev_char,
-                            include_next_char
-                        );
+            .map(|members| {
+                let (numbers, symbols) = members
+                    .into_iter()
+                    .map(|idx| &self.grid.spots[idx])
+                    .partition(|spot| spot.value.is_number());
+                Cluster { numbers, symbols }
+            })
+            .collect()
+    }
+
+    /// Spot indices with at least one edge, and every edge as
+    /// `(symbol index, number index)`.
+    fn edges(&self) -> (Vec<usize>, Vec<(usize, usize)>) {
+        let nodes = (0..self.neighbours.len())
+            .filter(|idx| !self.neighbours[*idx].is_empty())
+            .collect();
+        let edges = (0..self.neighbours.len())
+            .filter(|idx| !self.grid.spots[*idx].value.is_number())
+            .flat_map(|sym| self.neighbours[sym].iter().map(move |num| (sym, *num)))
+            .collect();
+        (nodes, edges)
+    }
 
-                        // let val_str: String = value.clone().into();
-                        let next_char_idx = char_idx + 1;
-                        let prev_char_idx = {
-                            if char_idx == 0 {
-                                None
-                            } else {
-                                Some(char_idx - 1)
-                            }
+    /// Graphviz source for the graph: boxes for symbols, ellipses for numbers,
+    /// each labelled with its value and position.
+    fn to_dot(&self) -> String {
+        let (nodes, edges) = self.edges();
+        let mut out = String::from("graph schematic {\n");
+        nodes.into_iter().for_each(|idx| {
+            let spot = &self.grid.spots[idx];
+            let shape = match spot.value.is_number() {
+                true => "ellipse",
+                false => "box",
+            };
+            let label = spot.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+            out.push_str(&format!(
+                "    s{} [label=\"{}\", shape={}];\n",
+                idx, label, shape
+            ));
+        });
+        edges.into_iter().for_each(|(sym, num)| {
+            out.push_str(&format!("    s{} -- s{};\n", sym, num));
+        });
+        out.push_str("}\n");
+        out
+    }
+
+    /// The same graph as `to_dot`, plus each cluster's members and total.
+    fn to_json(&self) -> String {
+        let (nodes, edges) = self.edges();
+        let nodes: Vec<String> = nodes
+            .into_iter()
+            .map(|idx| {
+                let spot = &self.grid.spots[idx];
+                let (row, col) = spot.positions[0];
+                let value = match &spot.value {
+                    GridValue::Number(n) => n.to_string(),
+                    GridValue::Character(c) => {
+                        let c = match c {
+                            '"' | '\\' => format!("\\{}", c),
+                            c => c.to_string(),
                         };
+                        format!("\"{}\"", c)
+                    }
+                };
+                format!(
+                    "{{\"id\":{},\"value\":{},\"row\":{},\"col\":{}}}",
+                    idx, value, row, col
+                )
+            })
+            .collect();
+        let edges: Vec<String> = edges
+            .into_iter()
+            .map(|(sym, num)| format!("[{},{}]", sym, num))
+            .collect();
+        let clusters: Vec<String> = self
+            .components()
+            .into_iter()
+            .map(|members| {
+                let total: u64 = members
+                    .iter()
+                    .filter_map(|idx| self.grid.spots[*idx].value.inner_num())
+                    .sum();
+                let members: Vec<String> = members.iter().map(|idx| idx.to_string()).collect();
+                format!(
+                    "{{\"members\":[{}],\"total\":{}}}",
+                    members.join(","),
+                    total
+                )
+            })
+            .collect();
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}],\"clusters\":[{}]}}",
+            nodes.join(","),
+            edges.join(","),
+            clusters.join(",")
+        )
+    }
 
-                        if include_next_char {
-                            adj_acc.push((line_num, next_char_idx))
-                        }
+    fn matching(
+        &self,
+        pred: impl Fn(&GridValue) -> bool,
+    ) -> Vec<(&'a GridSpot, Vec<&'a GridSpot>)> {
+        self.grid
+            .spots
+            .iter()
+            .enumerate()
+            .filter(|(_, spot)| pred(&spot.value))
+            .map(|(idx, _)| self.with_neighbours(idx))
+            .collect()
+    }
+}
 
-                        if include_prev_char {
-                            adj_acc.push((line_num, prev_char_idx.unwrap()))
-                        }
+impl Neighborhood {
+    fn radius(&self) -> usize {
+        match self {
+            Self::FourConnected | Self::EightConnected => 1,
+            Self::Chebyshev(n) => *n,
+        }
+    }
 
-                        if include_prev_line {
-                            adj_acc.push((line_num - 1, char_idx));
-                            if include_next_char {
-                                adj_acc.push((line_num - 1, next_char_idx))
-                            }
-                            if include_prev_char {
-                                adj_acc.push((line_num - 1, prev_char_idx.unwrap()))
-                            }
-                        }
+    /// How far the neighborhood extends sideways `row_offset` rows away.
+    fn horizontal_reach(&self, row_offset: usize) -> usize {
+        match self {
+            Self::FourConnected => 1 - row_offset,
+            _ => self.radius(),
+        }
+    }
 
-                        if include_next_line {
-                            adj_acc.push((line_num + 1, char_idx));
-                            if include_next_char {
-                                adj_acc.push((line_num + 1, next_char_idx))
-                            }
-                            if include_prev_char {
-                                adj_acc.push((line_num + 1, prev_char_idx.unwrap()))
-                            }
+    /// Cells touching a horizontal run of `positions`, clipped to `rows` lines
+    /// and `cols` columns.
+    fn cells(&self, positions: &[(usize, usize)], rows: usize, cols: usize) -> Vec<(usize, usize)> {
+        let first_col = positions.first().map(|p| p.1).unwrap_or_default();
+        let last_col = positions.last().map(|p| p.1).unwrap_or_default();
+        let radius = self.radius();
+        positions
+            .iter()
+            .fold(vec![], |mut adj_acc, &(line_idx, char_idx)| {
+                let prev_lines = (1..=radius.min(line_idx)).map(|d| (line_idx - d, d));
+                let next_lines = (1..=radius)
+                    .take_while(|d| line_idx + d < rows)
+                    .map(|d| (line_idx + d, d));
+                std::iter::once((line_idx, 0))
+                    .chain(prev_lines)
+                    .chain(next_lines)
+                    .for_each(|(row, offset)| {
+                        if offset != 0 {
+                            adj_acc.push((row, char_idx));
+                        }
+                        let reach = self.horizontal_reach(offset);
+                        if char_idx == last_col {
+                            (1..=reach)
+                                .take_while(|k| char_idx + k < cols)
+                                .for_each(|k| adj_acc.push((row, char_idx + k)));
+                        }
+                        if char_idx == first_col {
+                            (1..=reach.min(char_idx))
+                                .for_each(|k| adj_acc.push((row, char_idx - k)));
                         }
+                    });
+                adj_acc
+            })
+    }
+}
 
-                        println!("AFTER PASS {:?}\n", adj_acc);
-                        adj_acc
-                    },
-                );
+/// `4`, `8`, or `r<N>` for a Chebyshev radius of `N`.
+impl std::str::FromStr for Neighborhood {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4" => Ok(Self::FourConnected),
+            "8" => Ok(Self::EightConnected),
+            _ => s
+                .strip_prefix('r')
+                .and_then(|n| n.parse().ok())
+                .map(Self::Chebyshev)
+                .ok_or(format!("{} is not a neighborhood (4, 8 or rN)", s)),
+        }
+    }
+}
 
-                let spot = GridSpot {
-                    value,
-                    positions,
-                    adjacencies,
-                    is_part: false,
-                };
-                return_spots.push(spot);
-                return_spots
-            })
+impl AdjacencyRules {
+    fn for_symbol(&self, c: char) -> Neighborhood {
+        self.per_symbol.get(&c).copied().unwrap_or(self.default)
     }
+}
 
-    fn check_for_parts(&mut self) {
-        let clone = self.0.clone();
-        let char_spot_positions = clone
+impl GridValue {
+    fn inner_num(&self) -> Option<u64> {
+        match self {
+            Self::Number(num) => Some(*num),
+            _ => None,
+        }
+    }
+    fn is_number(&self) -> bool {
+        matches!(self, GridValue::Number(_))
+    }
+}
+
+impl Grid {
+    #[cfg(test)]
+    fn from_input_string(input: &str) -> Self {
+        Self::from_input_string_with_rules(input, AdjacencyRules::default())
+    }
+
+    fn from_input_string_with_rules(input: &str, rules: AdjacencyRules) -> Self {
+        let lines: Vec<&str> = input
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let line_count = lines.len();
+        let width = lines.iter().map(|l| l.chars().count()).max();
+        let mut grid = Self {
+            spots: vec![],
+            rules,
+            dims: (line_count, width.unwrap_or(0)),
+        };
+        lines.into_iter().enumerate().for_each(|(i, l)| {
+            let mut spots = Self::from_line_info(l, i);
+            grid.spots.append(&mut spots)
+        });
+        grid.check_for_parts();
+        grid
+    }
+
+    /// Validates `input` before building the grid, failing with every issue
+    /// found. With `RaggedRows::Pad`, short rows are accepted instead of
+    /// reported; their missing cells read as `.`.
+    fn parse(
+        input: &str,
+        rules: AdjacencyRules,
+        ragged: RaggedRows,
+    ) -> Result<Self, SchematicError> {
+        let issues: Vec<SchematicIssue> = validate_schematic(input)
             .into_iter()
-            .filter(|spot| spot.value.is_character())
-            .fold(vec![], |mut acc, mut spot| {
-                println!("Spot: {:?}", spot);
-                acc.append(&mut spot.positions);
-                acc
+            .filter(|issue| {
+                ragged == RaggedRows::Reject || !matches!(issue, SchematicIssue::RaggedRow { .. })
+            })
+            .collect();
+        if !issues.is_empty() {
+            return Err(SchematicError(issues));
+        }
+        Ok(Self::from_input_string_with_rules(input, rules))
+    }
+
+    /// Scans a line left to right, emitting one spot per number (spanning all of
+    /// its digits) and one per symbol, in order of appearance. `.` and
+    /// whitespace are empty cells.
+    fn from_line_info(line: &str, line_num: usize) -> Vec<GridSpot> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut spots = vec![];
+        let mut col = 0;
+        while col < chars.len() {
+            let start = col;
+            let value = if chars[col].is_ascii_digit() {
+                while col < chars.len() && chars[col].is_ascii_digit() {
+                    col += 1;
+                }
+                let digits: String = chars[start..col].iter().collect();
+                GridValue::try_from(digits.as_str()).expect("Spot value get failure")
+            } else {
+                col += 1;
+                match chars[start] {
+                    '.' => continue,
+                    c if c.is_whitespace() => continue,
+                    c => GridValue::Character(c),
+                }
+            };
+            let positions: Vec<(usize, usize)> = (start..col).map(|c| (line_num, c)).collect();
+            spots.push(GridSpot {
+                value,
+                positions,
+                is_part: false,
             });
+        }
+        spots
+    }
 
-        self.0
+    fn check_for_parts(&mut self) {
+        let parts: Vec<bool> = self
+            .adjacency_index()
+            .neighbours
+            .iter()
+            .map(|n| !n.is_empty())
+            .collect();
+        self.spots
             .iter_mut()
-            .filter(|spot| spot.value.is_number())
-            .for_each(|num_spot| {
-                if num_spot
-                    .adjacencies
-                    .iter()
-                    .any(|pos| char_spot_positions.contains(pos))
-                {
-                    num_spot.is_part = true;
-                }
-            })
+            .zip(parts)
+            .filter(|(spot, _)| spot.value.is_number())
+            .for_each(|(num_spot, is_part)| num_spot.is_part = is_part)
     }
 
     fn get_sum_of_all_part_numbers(&self) -> u64 {
-        self.0
+        self.spots
             .iter()
-            .filter_map(|s| {
-                if s.is_part {
-                    if let Some(num) = s.value.inner_num() {
-                        Some(num)
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+            .filter(|s| s.is_part)
+            .filter_map(|s| s.value.inner_num())
+            .sum()
+    }
+
+    /// A gear is a `*` touching exactly two numbers; its ratio is their product.
+    fn get_sum_of_gear_ratios(&self) -> u64 {
+        self.adjacency_index()
+            .by_symbol('*')
+            .into_iter()
+            .filter_map(|(_, touching)| match touching.as_slice() {
+                [a, b] => Some(a.value.inner_num()? * b.value.inner_num()?),
+                _ => None,
             })
             .sum()
     }
+
+    fn adjacency_index(&self) -> AdjacencyIndex<'_> {
+        AdjacencyIndex::new(self)
+    }
+
+    /// The schematic as rows of characters tagged with their highlight. Rows
+    /// are padded with `.` to the widest row.
+    fn annotated_cells(&self) -> Vec<Vec<(char, CellKind)>> {
+        let (rows, cols) = self.dims;
+        let mut cells = vec![vec![('.', CellKind::Empty); cols]; rows];
+        let gears: HashSet<(usize, usize)> = self
+            .adjacency_index()
+            .by_symbol('*')
+            .into_iter()
+            .filter(|(_, touching)| touching.len() == 2)
+            .map(|(gear, _)| gear.positions[0])
+            .collect();
+        self.spots.iter().for_each(|spot| {
+            let kind = match spot.value {
+                GridValue::Number(_) if spot.is_part => CellKind::Part,
+                GridValue::Number(_) => CellKind::NonPart,
+                GridValue::Character(_) if gears.contains(&spot.positions[0]) => CellKind::Gear,
+                GridValue::Character(_) => CellKind::Symbol,
+            };
+            let text: String = spot.value.clone().into();
+            spot.positions
+                .iter()
+                .zip(text.chars())
+                .for_each(|(&(row, col), c)| cells[row][col] = (c, kind));
+        });
+        cells
+    }
+
+    /// `annotated_cells` with consecutive cells of the same kind merged.
+    fn annotated_runs(&self) -> Vec<Vec<(String, CellKind)>> {
+        self.annotated_cells()
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .fold(vec![], |mut runs: Vec<(String, CellKind)>, (c, kind)| {
+                        match runs.last_mut() {
+                            Some((text, last)) if *last == kind => text.push(c),
+                            _ => runs.push((c.to_string(), kind)),
+                        }
+                        runs
+                    })
+            })
+            .collect()
+    }
+
+    /// Parts green, non-parts red, symbols yellow and gears bold magenta.
+    fn render_ansi(&self) -> String {
+        let mut out = String::new();
+        self.annotated_runs().into_iter().for_each(|row| {
+            row.into_iter().for_each(|(text, kind)| {
+                let code = match kind {
+                    CellKind::Empty => None,
+                    CellKind::Part => Some("32"),
+                    CellKind::NonPart => Some("31"),
+                    CellKind::Symbol => Some("33"),
+                    CellKind::Gear => Some("1;35"),
+                };
+                match code {
+                    Some(code) => out.push_str(&format!("\x1b[{}m{}\x1b[0m", code, text)),
+                    None => out.push_str(&text),
+                }
+            });
+            out.push('\n');
+        });
+        out
+    }
+
+    /// A standalone HTML page using the same palette as `render_ansi`.
+    fn render_html(&self) -> String {
+        let mut body = String::new();
+        self.annotated_runs().into_iter().for_each(|row| {
+            row.into_iter().for_each(|(text, kind)| {
+                let text = text
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;");
+                let class = match kind {
+                    CellKind::Empty => None,
+                    CellKind::Part => Some("part"),
+                    CellKind::NonPart => Some("non-part"),
+                    CellKind::Symbol => Some("symbol"),
+                    CellKind::Gear => Some("gear"),
+                };
+                match class {
+                    Some(class) => {
+                        body.push_str(&format!("<span class=\"{}\">{}</span>", class, text))
+                    }
+                    None => body.push_str(&text),
+                }
+            });
+            body.push('\n');
+        });
+        format!(
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Schematic</title>
+<style>
+body {{ background: #111; color: #666; }}
+.part {{ color: #4c4; }}
+.non-part {{ color: #d44; }}
+.symbol {{ color: #dd4; }}
+.gear {{ color: #d4d; font-weight: bold; }}
+</style>
+</head>
+<body>
+<pre>
+{}</pre>
+</body>
+</html>
+",
+            body
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Grid, GridSpot, GridValue};
+    use std::collections::{HashMap, HashSet};
+
+    use crate::{
+        generate_schematic, validate_schematic, AdjacencyRules, Grid, GridSpot, GridValue,
+        Neighborhood, RaggedRows, SchematicIssue,
+    };
+
+    const EXAMPLE: &str = "467..114..\n
+            ...*......\n
+            ..35..633.\n
+            ......#...\n
+            617*......\n
+            .....+.58.\n
+            ..592.....\n
+            ......755.\n
+            ...$.*....\n
+            .664.598..\n";
+
+    /// Cells in the default neighborhood of the spot at `idx`.
+    fn adjacencies(grid: &Grid, idx: usize) -> Vec<(usize, usize)> {
+        let (rows, cols) = grid.dims;
+        grid.rules
+            .default
+            .cells(&grid.spots[idx].positions, rows, cols)
+    }
 
     #[test]
     fn get_spot_from_single_line() {
@@ -277,11 +1014,11 @@ mod tests {
         let spot1 = GridSpot {
             value: GridValue::Number(467),
             positions: vec![(0, 0), (0, 1), (0, 2)],
-            adjacencies: vec![(0, 3)],
             is_part: false,
         };
         // println!("{:?}", grid);
-        assert_eq!(spot1, grid.0[0]);
+        assert_eq!(spot1, grid.spots[0]);
+        assert_eq!(vec![(0, 3)], adjacencies(&grid, 0));
     }
 
     #[test]
@@ -294,13 +1031,16 @@ mod tests {
         let spot1 = GridSpot {
             value: GridValue::Number(467),
             positions: vec![(0, 0), (0, 1), (0, 2)],
-            adjacencies: vec![(1, 0), (1, 1), (0, 3), (1, 2), (1, 3)],
             is_part: true,
         };
 
         // println!("{:?}", grid);
-        assert_eq!(spot1, grid.0[0]);
-        assert!(!grid.0[1].is_part);
+        assert_eq!(spot1, grid.spots[0]);
+        assert_eq!(
+            vec![(1, 0), (1, 1), (0, 3), (1, 2), (1, 3)],
+            adjacencies(&grid, 0)
+        );
+        assert!(!grid.spots[1].is_part);
     }
 
     #[test]
@@ -321,4 +1061,298 @@ mod tests {
         // println!("{:?}", grid);
         assert_eq!(4361u64, grid.get_sum_of_all_part_numbers());
     }
+
+    #[test]
+    fn repeated_and_nested_numbers_keep_their_own_positions() {
+        let lines = "4.467*4..\n
+            ..........";
+        let grid = Grid::from_input_string(lines);
+        let spans: Vec<(GridValue, Vec<(usize, usize)>)> = grid
+            .spots
+            .iter()
+            .map(|s| (s.value.clone(), s.positions.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                (GridValue::Number(4), vec![(0, 0)]),
+                (GridValue::Number(467), vec![(0, 2), (0, 3), (0, 4)]),
+                (GridValue::Character('*'), vec![(0, 5)]),
+                (GridValue::Number(4), vec![(0, 6)]),
+            ],
+            spans
+        );
+        assert!(!grid.spots[0].is_part);
+        assert!(grid.spots[1].is_part);
+        assert!(grid.spots[3].is_part);
+    }
+
+    #[test]
+    fn symbols_touching_digits_are_split_out() {
+        let lines = "12#34\n
+            5$$..";
+        let grid = Grid::from_input_string(lines);
+        let values: Vec<GridValue> = grid.spots.iter().map(|s| s.value.clone()).collect();
+        assert_eq!(
+            vec![
+                GridValue::Number(12),
+                GridValue::Character('#'),
+                GridValue::Number(34),
+                GridValue::Number(5),
+                GridValue::Character('$'),
+                GridValue::Character('$'),
+            ],
+            values
+        );
+        assert_eq!(51u64, grid.get_sum_of_all_part_numbers());
+    }
+
+    #[test]
+    fn pt_two_example() {
+        let grid = Grid::from_input_string(EXAMPLE);
+        assert_eq!(467835u64, grid.get_sum_of_gear_ratios());
+    }
+
+    #[test]
+    fn adjacency_index_queries() {
+        let grid = Grid::from_input_string(EXAMPLE);
+        let index = grid.adjacency_index();
+        let values = |spots: Vec<&GridSpot>| -> Vec<GridValue> {
+            spots.into_iter().map(|s| s.value.clone()).collect()
+        };
+
+        let stars = index.by_symbol('*');
+        assert_eq!(3, stars.len());
+        assert_eq!(vec![(1, 3)], stars[0].0.positions);
+        assert_eq!(
+            vec![GridValue::Number(467), GridValue::Number(35)],
+            values(stars[0].1.clone())
+        );
+        assert_eq!(vec![GridValue::Number(617)], values(stars[1].1.clone()));
+
+        let (spot, symbols) = index.at((2, 7)).unwrap();
+        assert_eq!(GridValue::Number(633), spot.value);
+        assert_eq!(vec![GridValue::Character('#')], values(symbols));
+        assert!(index.at((0, 4)).is_none());
+
+        let (_, symbols) = index.by_number(114).pop().unwrap();
+        assert!(symbols.is_empty());
+        let (_, symbols) = index.by_number(592).pop().unwrap();
+        assert_eq!(vec![GridValue::Character('+')], values(symbols));
+    }
+
+    #[test]
+    fn neighborhood_rules() {
+        let lines = "1..3.\n
+            .#...\n
+            4...5\n
+            ..*..";
+        let parts = |rules: AdjacencyRules| -> Vec<u64> {
+            Grid::from_input_string_with_rules(lines, rules)
+                .spots
+                .iter()
+                .filter(|s| s.is_part)
+                .filter_map(|s| s.value.inner_num())
+                .collect()
+        };
+
+        assert_eq!(vec![1, 4], parts(AdjacencyRules::default()));
+        assert_eq!(
+            Vec::<u64>::new(),
+            parts(AdjacencyRules {
+                default: Neighborhood::FourConnected,
+                per_symbol: HashMap::new(),
+            })
+        );
+        assert_eq!(
+            vec![1, 3, 4, 5],
+            parts(AdjacencyRules {
+                default: Neighborhood::Chebyshev(2),
+                per_symbol: HashMap::new(),
+            })
+        );
+        assert_eq!(
+            vec![1, 3, 4],
+            parts(AdjacencyRules {
+                default: Neighborhood::EightConnected,
+                per_symbol: HashMap::from([('#', Neighborhood::Chebyshev(2))]),
+            })
+        );
+
+        let grid = Grid::from_input_string_with_rules(
+            "..1..\n..*..",
+            AdjacencyRules {
+                default: Neighborhood::FourConnected,
+                per_symbol: HashMap::new(),
+            },
+        );
+        assert_eq!(vec![(0, 3), (0, 1), (1, 2)], adjacencies(&grid, 0));
+        assert!(grid.spots[0].is_part);
+    }
+
+    #[test]
+    fn render_highlights_parts_symbols_and_gears() {
+        let grid = Grid::from_input_string("12*3.\n....7\n&.5..");
+        let expected = "\x1b[32m12\x1b[0m\x1b[1;35m*\x1b[0m\x1b[32m3\x1b[0m.\n\
+            ....\x1b[31m7\x1b[0m\n\
+            \x1b[33m&\x1b[0m.\x1b[31m5\x1b[0m..\n";
+        assert_eq!(expected, grid.render_ansi());
+
+        let html = grid.render_html();
+        assert!(html.contains("<span class=\"gear\">*</span>"));
+        assert!(html.contains("<span class=\"symbol\">&amp;</span>"));
+        assert!(html.contains("<span class=\"non-part\">7</span>"));
+    }
+
+    #[test]
+    fn dense_index_matches_naive_part_check() {
+        let grid = Grid::from_input_string(&generate_schematic(80, 7));
+        let symbol_cells: HashSet<(usize, usize)> = grid
+            .spots
+            .iter()
+            .filter(|s| !s.value.is_number())
+            .flat_map(|s| s.positions.clone())
+            .collect();
+        grid.spots
+            .iter()
+            .filter(|s| s.value.is_number())
+            .for_each(|spot| {
+                let naive = grid
+                    .rules
+                    .default
+                    .cells(&spot.positions, 80, 80)
+                    .iter()
+                    .any(|a| symbol_cells.contains(a));
+                assert_eq!(naive, spot.is_part, "{:?}", spot);
+            });
+        assert_eq!((80, 80), grid.dims);
+    }
+
+    #[test]
+    fn validation_reports_issues_with_coordinates() {
+        let input = "467..114..\n
+            ...*..\u{7}...\n
+            ..35..a33.\n
+            ..99999999999999999999..\n
+            617*..";
+        assert_eq!(
+            vec![
+                SchematicIssue::RaggedRow {
+                    line: 1,
+                    len: 10,
+                    expected: 24
+                },
+                SchematicIssue::RaggedRow {
+                    line: 3,
+                    len: 10,
+                    expected: 24
+                },
+                SchematicIssue::NonPrintable {
+                    line: 3,
+                    col: 19,
+                    c: '\u{7}'
+                },
+                SchematicIssue::RaggedRow {
+                    line: 5,
+                    len: 10,
+                    expected: 24
+                },
+                SchematicIssue::UnexpectedToken {
+                    line: 5,
+                    col: 19,
+                    token: "a".to_string()
+                },
+                SchematicIssue::UnexpectedToken {
+                    line: 7,
+                    col: 15,
+                    token: "99999999999999999999".to_string()
+                },
+                SchematicIssue::RaggedRow {
+                    line: 9,
+                    len: 6,
+                    expected: 24
+                },
+            ],
+            validate_schematic(input)
+        );
+        assert!(validate_schematic(EXAMPLE).is_empty());
+    }
+
+    #[test]
+    fn ragged_rows_are_rejected_or_padded() {
+        let input = "467..114..\n...*\n..35..633.";
+        let err = Grid::parse(input, AdjacencyRules::default(), RaggedRows::Reject).unwrap_err();
+        assert_eq!(
+            vec![SchematicIssue::RaggedRow {
+                line: 2,
+                len: 4,
+                expected: 10
+            }],
+            err.0
+        );
+        assert_eq!(
+            "Invalid schematic (1 issues):\n  line 2: 4 columns wide, expected 10\n",
+            err.to_string()
+        );
+
+        let grid = Grid::parse(input, AdjacencyRules::default(), RaggedRows::Pad).unwrap();
+        let padded = Grid::from_input_string("467..114..\n...*......\n..35..633.");
+        assert_eq!(padded, grid);
+        assert_eq!((3, 10), grid.dims);
+        assert_eq!(502, grid.get_sum_of_all_part_numbers());
+        assert!(Grid::parse("1a2", AdjacencyRules::default(), RaggedRows::Pad).is_err());
+    }
+
+    #[test]
+    fn clusters_and_graph_export() {
+        let grid = Grid::from_input_string("12*3..\n....7.\n4#..*5\n......");
+        let index = grid.adjacency_index();
+        let clusters = index.clusters();
+        let summary: Vec<(Vec<GridValue>, u64)> = clusters
+            .iter()
+            .map(|c| {
+                let values = c
+                    .symbols
+                    .iter()
+                    .chain(&c.numbers)
+                    .map(|s| s.value.clone())
+                    .collect();
+                (values, c.total())
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    vec![
+                        GridValue::Character('*'),
+                        GridValue::Number(12),
+                        GridValue::Number(3)
+                    ],
+                    15
+                ),
+                (
+                    vec![
+                        GridValue::Character('*'),
+                        GridValue::Number(7),
+                        GridValue::Number(5)
+                    ],
+                    12
+                ),
+                (vec![GridValue::Character('#'), GridValue::Number(4)], 4),
+            ],
+            summary
+        );
+
+        let dot = index.to_dot();
+        assert!(
+            dot.starts_with("graph schematic {\n    s0 [label=\"12 @ (0, 0)\", shape=ellipse];\n")
+        );
+        assert!(dot.contains("    s1 -- s0;\n    s1 -- s2;\n"));
+        assert!(dot.ends_with("}\n"));
+
+        let json = index.to_json();
+        assert!(json.contains("{\"id\":1,\"value\":\"*\",\"row\":0,\"col\":2}"));
+        assert!(json.ends_with(
+            "\"clusters\":[{\"members\":[1,0,2],\"total\":15},{\"members\":[6,3,7],\"total\":12},{\"members\":[5,4],\"total\":4}]}"
+        ));
+    }
 }
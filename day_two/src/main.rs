@@ -1,9 +1,12 @@
-use std::{cmp::max, env, fs};
+use std::{cmp::max, env, fmt, fs};
 
 fn main() {
-    let mut args = env::args();
-    if args.len() != 2 {
-        println!("Usage: cargo run -- <pt1 or pt2>");
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!(
+            "Usage: cargo run -- <pt1, pt2, print, stats [json] \
+             or optimize <budget> [<red> <green> <blue>]>"
+        );
         return;
     }
 
@@ -13,32 +16,157 @@ fn main() {
         red: 12,
         green: 13,
     };
-    match args.nth(1).as_deref() {
-        Some("pt1") => println!("{}", solve_for_pt_1(&input, predicate)),
-        Some("pt2") => println!("{}", solve_for_pt_2(&input)),
-        _ => println!("Invalid argument. Use 'pt1' or 'pt2'."),
+    match args[1].as_str() {
+        "pt1" => println!("{}", solve_for_pt_1(&input, predicate)),
+        "pt2" => println!("{}", solve_for_pt_2(&input)),
+        "print" => print!("{}", serialize_games(&input)),
+        "optimize" => {
+            let nums: Vec<u32> = args[2..]
+                .iter()
+                .map(|a| a.parse().expect("Bag arguments must be integers"))
+                .collect();
+            let limits = match nums.len() {
+                1 => None,
+                4 => Some(Predicate {
+                    red: nums[1],
+                    green: nums[2],
+                    blue: nums[3],
+                }),
+                _ => {
+                    println!("Usage: cargo run -- optimize <budget> [<red> <green> <blue>]");
+                    return;
+                }
+            };
+            let games = parse_games(&input);
+            let ranked = search_bags(&games, nums[0], limits.as_ref(), 5);
+            for (i, scored) in ranked.iter().enumerate() {
+                println!(
+                    "{}. {} red, {} green, {} blue => {}",
+                    i + 1,
+                    scored.bag.red,
+                    scored.bag.green,
+                    scored.bag.blue,
+                    scored.id_sum
+                );
+            }
+        }
+        "stats" => {
+            let report = StatsReport::from(parse_games(&input).as_slice());
+            match args.get(2).map(|a| a.as_str()) {
+                Some("json") => println!("{}", report.to_json()),
+                _ => print!("{}", report),
+            }
+        }
+        _ => println!("Invalid argument. Use 'pt1', 'pt2', 'print', 'stats' or 'optimize'."),
     }
 }
 
-fn solve_for_pt_1(input: &str, predicate: Predicate) -> u32 {
+fn parse_games(input: &str) -> Vec<Game> {
     let lines = input.lines().filter(|l| !l.trim().is_empty());
-    let games = lines.map(|l| Game::try_from(l.trim()).unwrap()).collect();
-    let games = find_possible_games(games, predicate);
+    lines.map(|l| Game::try_from(l.trim()).unwrap()).collect()
+}
+
+fn solve_for_pt_1(input: &str, predicate: Predicate) -> u32 {
+    let games = find_possible_games(parse_games(input), predicate);
     games.iter().fold(0, |acc, game| acc + game.id)
 }
 
 fn solve_for_pt_2(input: &str) -> u64 {
-    let lines = input.lines().filter(|l| !l.trim().is_empty());
-    let games: Vec<Game> = lines.map(|l| Game::try_from(l.trim()).unwrap()).collect();
+    let games = parse_games(input);
     games.into_iter().map(|g| g.power_of_cubes()).sum()
 }
 
+/// Re-emits every game in the input as normalized puzzle text, one per line.
+fn serialize_games(input: &str) -> String {
+    parse_games(input)
+        .into_iter()
+        .map(|g| format!("{}\n", g))
+        .collect()
+}
+
+/// Searches every bag holding at most `budget` cubes (and at most `limits` of
+/// each color, if given) and returns the `top` bags with the highest sum of
+/// possible game ids, best first.
+///
+/// The sum only changes when a color count crosses one of the per-game
+/// maximums, so only those counts are tried. Each bag is shrunk to the
+/// largest per-game maximums among the games it allows, so no bag is
+/// reported twice; ties go to the bag with fewer cubes.
+fn search_bags(
+    games: &[Game],
+    budget: u32,
+    limits: Option<&Predicate>,
+    top: usize,
+) -> Vec<BagScore> {
+    let candidates = |count: fn(&Game) -> u32, limit: u32| {
+        let mut counts: Vec<u32> = games
+            .iter()
+            .map(count)
+            .chain([0])
+            .filter(|c| *c <= limit.min(budget))
+            .collect();
+        counts.sort_unstable();
+        counts.dedup();
+        counts
+    };
+    let (red_limit, green_limit, blue_limit) = limits
+        .map(|l| (l.red, l.green, l.blue))
+        .unwrap_or((u32::MAX, u32::MAX, u32::MAX));
+    let reds = candidates(|g| g.red, red_limit);
+    let greens = candidates(|g| g.green, green_limit);
+    let blues = candidates(|g| g.blue, blue_limit);
+
+    let mut scored: Vec<BagScore> = vec![];
+    for red in &reds {
+        for green in greens.iter().take_while(|g| red + *g <= budget) {
+            let remaining = budget - red - green;
+            let Some(blue) = blues.iter().rev().find(|b| **b <= remaining) else {
+                continue;
+            };
+            let bag = Predicate {
+                blue: *blue,
+                red: *red,
+                green: *green,
+            };
+            let bag = games.iter().filter(|g| bag.game_possible(g)).fold(
+                Predicate {
+                    blue: 0,
+                    red: 0,
+                    green: 0,
+                },
+                |shrunk, g| Predicate {
+                    blue: shrunk.blue.max(g.blue),
+                    red: shrunk.red.max(g.red),
+                    green: shrunk.green.max(g.green),
+                },
+            );
+            let id_sum = bag.id_sum(games);
+            scored.push(BagScore { bag, id_sum });
+        }
+    }
+    scored.sort_by_key(|s| {
+        let bag = &s.bag;
+        (std::cmp::Reverse(s.id_sum), bag.cubes(), bag.red, bag.green)
+    });
+    scored.dedup();
+    scored.truncate(top);
+    scored
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Game {
     id: u32,
     blue: u32,
     red: u32,
     green: u32,
+    reveals: Vec<Reveal>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reveal {
+    blue: u32,
+    red: u32,
+    green: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -48,13 +176,48 @@ pub struct Predicate {
     green: u32,
 }
 
+/// Distribution of cube counts for one color across every game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorStats {
+    color: &'static str,
+    /// `(max count in a game, number of games)`, ascending by count.
+    max_histogram: Vec<(u32, usize)>,
+    mean_per_reveal: f64,
+    median_per_reveal: f64,
+    /// Smallest per-game maximum and the ids of the games that have it.
+    lowest_max: (u32, Vec<u32>),
+    /// Largest per-game maximum and the ids of the games that have it.
+    highest_max: (u32, Vec<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerStats {
+    min: u64,
+    lower_quartile: f64,
+    median: f64,
+    upper_quartile: f64,
+    max: u64,
+    mean: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsReport {
+    games: usize,
+    reveals: usize,
+    colors: Vec<ColorStats>,
+    power: PowerStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagScore {
+    bag: Predicate,
+    id_sum: u32,
+}
+
 impl<'a> TryFrom<&'a str> for Game {
     type Error = Box<dyn std::error::Error>;
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
         if let Some((id_chunk, blocks_chunk)) = value.split_once(':') {
-            let (mut blue, mut red, mut green) = (0, 0, 0);
-            let colors = vec!["blue", "red", "green"];
-
             let id: u32 = id_chunk
                 .chars()
                 .filter(|c| c.is_numeric())
@@ -62,58 +225,281 @@ impl<'a> TryFrom<&'a str> for Game {
                 .parse::<u32>()
                 .expect("Failed to get int from ID chunk");
 
-            blocks_chunk.split(';').into_iter().for_each(|reveal| {
-                let mut blocks_chunks: Vec<&str> = reveal.split(',').collect();
-                let (mut inner_blue, mut inner_red, mut inner_green) = (0, 0, 0);
-                colors.iter().for_each(|color| {
-                    blocks_chunks.iter_mut().for_each(|ch| {
-                        if ch.contains(color) {
-                            if let Some(num) = ch
-                                .chars()
-                                .filter(|c| c.is_numeric())
-                                .collect::<String>()
-                                .parse::<u32>()
-                                .ok()
-                            {
-                                match color.to_owned() {
-                                    "blue" => inner_blue += num,
-                                    "green" => inner_green += num,
-                                    "red" => inner_red += num,
-                                    _ => panic!("No other color value should exist!"),
-                                }
-                            }
-                        }
-                    })
-                });
-                blue = max(blue, inner_blue);
-                green = max(green, inner_green);
-                red = max(red, inner_red);
-            });
-
-            Ok(Game {
-                id,
-                blue,
-                red,
-                green,
-            })
+            let reveals: Vec<Reveal> = blocks_chunk.split(';').map(Reveal::from).collect();
+            Ok(Game::new(id, reveals))
         } else {
             Err(format!("{} could not be split at :", value).into())
         }
     }
 }
 
+impl From<&str> for Reveal {
+    fn from(value: &str) -> Self {
+        let colors = ["blue", "red", "green"];
+        let mut blocks_chunks: Vec<&str> = value.split(',').collect();
+        let mut reveal = Reveal::default();
+        colors.iter().for_each(|color| {
+            blocks_chunks.iter_mut().for_each(|ch| {
+                if ch.contains(color) {
+                    if let Ok(num) = ch
+                        .chars()
+                        .filter(|c| c.is_numeric())
+                        .collect::<String>()
+                        .parse::<u32>()
+                    {
+                        match color.to_owned() {
+                            "blue" => reveal.blue += num,
+                            "green" => reveal.green += num,
+                            "red" => reveal.red += num,
+                            _ => panic!("No other color value should exist!"),
+                        }
+                    }
+                }
+            })
+        });
+        reveal
+    }
+}
+
+/// Reveals are written in a fixed `red, green, blue` order, skipping colors
+/// that were not drawn.
+impl fmt::Display for Reveal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blocks: Vec<String> = [
+            (self.red, "red"),
+            (self.green, "green"),
+            (self.blue, "blue"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, color)| format!("{} {}", count, color))
+        .collect();
+        write!(f, "{}", blocks.join(", "))
+    }
+}
+
+/// Canonical puzzle text for a game, e.g. `Game 1: 4 red, 3 blue; 2 green`.
+/// Parsing the output yields an equal `Game`.
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reveals: Vec<String> = self.reveals.iter().map(|r| r.to_string()).collect();
+        write!(f, "Game {}: {}", self.id, reveals.join("; "))
+    }
+}
+
 impl Predicate {
     pub fn game_possible(&self, game: &Game) -> bool {
         game.blue <= self.blue && game.green <= self.green && game.red <= self.red
     }
+
+    pub fn id_sum(&self, games: &[Game]) -> u32 {
+        games
+            .iter()
+            .filter(|g| self.game_possible(g))
+            .map(|g| g.id)
+            .sum()
+    }
+
+    pub fn cubes(&self) -> u32 {
+        self.blue + self.red + self.green
+    }
 }
 
 impl Game {
+    pub fn new(id: u32, reveals: Vec<Reveal>) -> Self {
+        let (blue, red, green) = reveals.iter().fold((0, 0, 0), |(b, r, g), reveal| {
+            (
+                max(b, reveal.blue),
+                max(r, reveal.red),
+                max(g, reveal.green),
+            )
+        });
+        Game {
+            id,
+            blue,
+            red,
+            green,
+            reveals,
+        }
+    }
+
     pub fn power_of_cubes(&self) -> u64 {
         (self.blue * self.green * self.red) as u64
     }
 }
 
+/// Linearly interpolated quantile of an already sorted, non-empty slice.
+fn quantile(sorted: &[u64], q: f64) -> f64 {
+    let pos = (sorted.len() - 1) as f64 * q;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] as f64 + (sorted[hi] as f64 - sorted[lo] as f64) * (pos - lo as f64)
+}
+
+fn mean(values: &[u64]) -> f64 {
+    values.iter().sum::<u64>() as f64 / values.len().max(1) as f64
+}
+
+impl ColorStats {
+    fn new(
+        color: &'static str,
+        games: &[Game],
+        game_max: fn(&Game) -> u32,
+        reveal_count: fn(&Reveal) -> u32,
+    ) -> Self {
+        let mut max_histogram: Vec<(u32, usize)> = vec![];
+        let mut maxes: Vec<u32> = games.iter().map(game_max).collect();
+        maxes.sort_unstable();
+        maxes.iter().for_each(|m| match max_histogram.last_mut() {
+            Some((count, games)) if count == m => *games += 1,
+            _ => max_histogram.push((*m, 1)),
+        });
+
+        let mut per_reveal: Vec<u64> = games
+            .iter()
+            .flat_map(|g| g.reveals.iter().map(|r| reveal_count(r) as u64))
+            .collect();
+        per_reveal.sort_unstable();
+
+        let extreme = |value: u32| {
+            let ids = games
+                .iter()
+                .filter(|g| game_max(g) == value)
+                .map(|g| g.id)
+                .collect();
+            (value, ids)
+        };
+        let lowest = maxes.first().copied().unwrap_or_default();
+        let highest = maxes.last().copied().unwrap_or_default();
+
+        ColorStats {
+            color,
+            max_histogram,
+            mean_per_reveal: mean(&per_reveal),
+            median_per_reveal: match per_reveal.is_empty() {
+                true => 0.0,
+                false => quantile(&per_reveal, 0.5),
+            },
+            lowest_max: extreme(lowest),
+            highest_max: extreme(highest),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let histogram: Vec<String> = self
+            .max_histogram
+            .iter()
+            .map(|(count, games)| format!("{{\"max\":{},\"games\":{}}}", count, games))
+            .collect();
+        let extreme = |(value, ids): &(u32, Vec<u32>)| {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            format!("{{\"value\":{},\"games\":[{}]}}", value, ids.join(","))
+        };
+        format!(
+            "{{\"color\":\"{}\",\"max_histogram\":[{}],\"mean_per_reveal\":{},\"median_per_reveal\":{},\"lowest_max\":{},\"highest_max\":{}}}",
+            self.color,
+            histogram.join(","),
+            self.mean_per_reveal,
+            self.median_per_reveal,
+            extreme(&self.lowest_max),
+            extreme(&self.highest_max)
+        )
+    }
+}
+
+impl From<&[Game]> for PowerStats {
+    fn from(games: &[Game]) -> Self {
+        let mut powers: Vec<u64> = games.iter().map(|g| g.power_of_cubes()).collect();
+        powers.sort_unstable();
+        if powers.is_empty() {
+            powers.push(0);
+        }
+        PowerStats {
+            min: powers[0],
+            lower_quartile: quantile(&powers, 0.25),
+            median: quantile(&powers, 0.5),
+            upper_quartile: quantile(&powers, 0.75),
+            max: powers[powers.len() - 1],
+            mean: mean(&powers),
+        }
+    }
+}
+
+impl From<&[Game]> for StatsReport {
+    fn from(games: &[Game]) -> Self {
+        StatsReport {
+            games: games.len(),
+            reveals: games.iter().map(|g| g.reveals.len()).sum(),
+            colors: vec![
+                ColorStats::new("red", games, |g| g.red, |r| r.red),
+                ColorStats::new("green", games, |g| g.green, |r| r.green),
+                ColorStats::new("blue", games, |g| g.blue, |r| r.blue),
+            ],
+            power: PowerStats::from(games),
+        }
+    }
+}
+
+impl StatsReport {
+    pub fn to_json(&self) -> String {
+        let colors: Vec<String> = self.colors.iter().map(|c| c.to_json()).collect();
+        let p = &self.power;
+        format!(
+            "{{\"games\":{},\"reveals\":{},\"colors\":[{}],\"power_of_cubes\":{{\"min\":{},\"lower_quartile\":{},\"median\":{},\"upper_quartile\":{},\"max\":{},\"mean\":{}}}}}",
+            self.games,
+            self.reveals,
+            colors.join(","),
+            p.min,
+            p.lower_quartile,
+            p.median,
+            p.upper_quartile,
+            p.max,
+            p.mean
+        )
+    }
+}
+
+/// Terminal tables: a summary row per color, then a histogram of per-game
+/// maximums for each color, then the power distribution.
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} games, {} reveals\n", self.games, self.reveals)?;
+        writeln!(
+            f,
+            "{:<6} | {:>9} | {:>11} | {:<24} | {:<24}",
+            "color", "mean/rev", "median/rev", "lowest max (games)", "highest max (games)"
+        )?;
+        writeln!(f, "{}", "-".repeat(86))?;
+        for c in &self.colors {
+            let extreme = |(value, ids): &(u32, Vec<u32>)| {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                format!("{} ({})", value, ids.join(", "))
+            };
+            writeln!(
+                f,
+                "{:<6} | {:>9.2} | {:>11.1} | {:<24} | {:<24}",
+                c.color,
+                c.mean_per_reveal,
+                c.median_per_reveal,
+                extreme(&c.lowest_max),
+                extreme(&c.highest_max)
+            )?;
+        }
+        for c in &self.colors {
+            writeln!(f, "\n{} max per game", c.color)?;
+            for (count, games) in &c.max_histogram {
+                writeln!(f, "{:>4} | {:>3} | {}", count, games, "#".repeat(*games))?;
+            }
+        }
+        let p = &self.power;
+        writeln!(f, "\npower of cubes")?;
+        writeln!(
+            f,
+            "min {} | q1 {:.1} | median {:.1} | q3 {:.1} | max {} | mean {:.2}",
+            p.min, p.lower_quartile, p.median, p.upper_quartile, p.max, p.mean
+        )
+    }
+}
+
 fn find_possible_games(games: Vec<Game>, predicate: Predicate) -> Vec<Game> {
     games
         .into_iter()
@@ -123,7 +509,18 @@ fn find_possible_games(games: Vec<Game>, predicate: Predicate) -> Vec<Game> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{solve_for_pt_1, solve_for_pt_2, Game, Predicate};
+    use crate::{
+        parse_games, search_bags, serialize_games, solve_for_pt_1, solve_for_pt_2, Game, Predicate,
+        Reveal, StatsReport,
+    };
+
+    const EXAMPLE: &str = "
+            Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+            Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+            Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+            Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+            Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+        ";
 
     #[test]
     fn parse_single_line_into_game() {
@@ -133,6 +530,23 @@ mod tests {
             blue: 6,
             red: 4,
             green: 2,
+            reveals: vec![
+                Reveal {
+                    blue: 3,
+                    red: 4,
+                    green: 0,
+                },
+                Reveal {
+                    blue: 6,
+                    red: 1,
+                    green: 2,
+                },
+                Reveal {
+                    blue: 0,
+                    red: 0,
+                    green: 2,
+                },
+            ],
         };
         assert_eq!(expected_game, Game::try_from(line).unwrap());
     }
@@ -165,4 +579,125 @@ mod tests {
         ";
         assert_eq!(2286, solve_for_pt_2(input));
     }
+
+    #[test]
+    fn serialize_normalizes_spacing_and_color_order() {
+        let line = "Game 3:8 green,  6 blue, 20 red;5 blue, 4 red, 13 green ;  5 green, 1 red";
+        let game = Game::try_from(line).unwrap();
+        assert_eq!(
+            "Game 3: 20 red, 8 green, 6 blue; 4 red, 13 green, 5 blue; 1 red, 5 green",
+            game.to_string()
+        );
+        assert_eq!(
+            "Game 1: 4 red, 3 blue; 1 red, 2 green, 6 blue; 2 green\n",
+            serialize_games("\n   Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green\n")
+        );
+    }
+
+    #[test]
+    fn parse_print_parse_round_trip() {
+        // Small xorshift so the property check stays deterministic without extra crates.
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = |bound: u32| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as u32
+        };
+
+        for _ in 0..500 {
+            let id = next(1000) + 1;
+            let reveals: Vec<Reveal> = (0..next(6) + 1)
+                .map(|_| Reveal {
+                    blue: next(21),
+                    red: next(21),
+                    green: next(21),
+                })
+                .collect();
+            let game = Game::new(id, reveals);
+            let printed = game.to_string();
+            let parsed = Game::try_from(printed.as_str()).unwrap();
+            assert_eq!(game, parsed, "{}", printed);
+            assert_eq!(printed, parsed.to_string());
+        }
+
+        let input = std::fs::read_to_string("src/input.txt").unwrap();
+        input
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .for_each(|l| {
+                let game = Game::try_from(l).unwrap();
+                assert_eq!(game, Game::try_from(game.to_string().as_str()).unwrap());
+            });
+    }
+
+    #[test]
+    fn search_bags_under_budget() {
+        let games = parse_games(EXAMPLE);
+
+        let ranked = search_bags(&games, 39, None, 3);
+        assert_eq!(12, ranked[0].id_sum);
+        assert_eq!(
+            Predicate {
+                blue: 15,
+                red: 14,
+                green: 3,
+            },
+            ranked[0].bag
+        );
+        assert!(ranked.windows(2).all(|w| w[0].id_sum >= w[1].id_sum));
+
+        let official = Predicate {
+            blue: 14,
+            red: 12,
+            green: 13,
+        };
+        let ranked = search_bags(&games, official.cubes(), Some(&official), 1);
+        assert_eq!(8, ranked[0].id_sum);
+
+        let ranked = search_bags(&games, 13, None, 1);
+        assert_eq!(
+            Predicate {
+                blue: 4,
+                red: 6,
+                green: 3,
+            },
+            ranked[0].bag
+        );
+        assert_eq!(7, ranked[0].id_sum);
+
+        // No game fits in two cubes, so every candidate shrinks to the empty bag.
+        let ranked = search_bags(&games, 2, None, 3);
+        assert_eq!(1, ranked.len());
+        assert_eq!((0, 0), (ranked[0].bag.cubes(), ranked[0].id_sum));
+    }
+
+    #[test]
+    fn stats_report_over_example() {
+        let report = StatsReport::from(parse_games(EXAMPLE).as_slice());
+        assert_eq!((5, 14), (report.games, report.reveals));
+
+        let red = &report.colors[0];
+        assert_eq!(
+            vec![(1, 1), (4, 1), (6, 1), (14, 1), (20, 1)],
+            red.max_histogram
+        );
+        assert_eq!((1, vec![2]), red.lowest_max);
+        assert_eq!((20, vec![3]), red.highest_max);
+        assert_eq!(2.0, red.median_per_reveal);
+        assert_eq!(61.0 / 14.0, red.mean_per_reveal);
+
+        let green = &report.colors[1];
+        assert_eq!(vec![(2, 1), (3, 3), (13, 1)], green.max_histogram);
+        assert_eq!((2, vec![1]), green.lowest_max);
+
+        assert_eq!((12, 1560), (report.power.min, report.power.max));
+        assert_eq!(48.0, report.power.median);
+        assert_eq!(2286.0 / 5.0, report.power.mean);
+
+        let json = report.to_json();
+        assert!(json.starts_with("{\"games\":5,\"reveals\":14,\"colors\":[{\"color\":\"red\""));
+        assert!(json.contains("\"highest_max\":{\"value\":20,\"games\":[3]}"));
+        assert!(json.ends_with("\"max\":1560,\"mean\":457.2}}"));
+    }
 }